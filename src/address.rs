@@ -0,0 +1,226 @@
+use crate::error::{Error, Result};
+use crate::socket::TLS_PREFIX;
+use std::env;
+
+/// Port used when none is given.
+const DEFAULT_PORT: &str = "6600";
+
+/// Host used when none is given.
+const DEFAULT_HOST: &str = "localhost";
+
+/// Server address, with the password to authenticate with if given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Address {
+    pub(crate) host: String,
+    pub(crate) password: Option<String>,
+}
+
+impl Address {
+    /// Reads the address from the `MPD_HOST`, `MPD_PORT` and `MPD_PASSWORD`
+    /// environment variables, following the conventions of other MPD clients.
+    pub(crate) fn from_env() -> Self {
+        Self::from_vars(
+            env::var("MPD_HOST").ok().as_deref(),
+            env::var("MPD_PORT").ok().as_deref(),
+            env::var("MPD_PASSWORD").ok().as_deref(),
+        )
+    }
+
+    /// Parses a connection string of the form `[tls://][password@]host[:port]`,
+    /// where the host can instead be a unix socket path or abstract socket name.
+    ///
+    /// The port defaults to `6600` for TCP hosts.
+    pub(crate) fn parse(address: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidAddress(format!("{reason} in `{address}`"));
+
+        let (scheme, rest) = match address.strip_prefix(TLS_PREFIX) {
+            Some(rest) => (TLS_PREFIX, rest),
+            None => ("", address),
+        };
+
+        let (password, host) = split_password(rest);
+        if host.is_empty() {
+            return Err(invalid("missing host"));
+        }
+
+        if is_socket_path(host) {
+            if host.len() == 1 {
+                return Err(invalid("missing socket name"));
+            }
+
+            return Ok(Self {
+                host: format!("{scheme}{host}"),
+                password: password.map(ToString::to_string),
+            });
+        }
+
+        let (name, port) = split_port(host).ok_or_else(|| invalid("invalid host"))?;
+        let port = port.unwrap_or(DEFAULT_PORT);
+        if name.is_empty() {
+            return Err(invalid("missing host"));
+        }
+        if port.parse::<u16>().is_err() {
+            return Err(invalid("invalid port"));
+        }
+
+        Ok(Self {
+            host: format!("{scheme}{}", with_port(name, port)),
+            password: password.map(ToString::to_string),
+        })
+    }
+
+    fn from_vars(host: Option<&str>, port: Option<&str>, password: Option<&str>) -> Self {
+        let (host_password, host) = split_password(host.unwrap_or_default());
+        let host = if host.is_empty() { DEFAULT_HOST } else { host };
+
+        let port = port.filter(|port| !port.is_empty()).unwrap_or(DEFAULT_PORT);
+        let host = if is_socket_path(host) {
+            host.to_string()
+        } else {
+            // A port in `MPD_HOST` takes precedence over `MPD_PORT`.
+            match split_port(host) {
+                Some((name, Some(host_port))) => with_port(name, host_port),
+                _ => with_port(host, port),
+            }
+        };
+
+        // A password in `MPD_HOST` takes precedence over `MPD_PASSWORD`.
+        let password = host_password
+            .or(password.filter(|password| !password.is_empty()))
+            .map(ToString::to_string);
+
+        Self { host, password }
+    }
+}
+
+/// Splits an optional `password@` prefix from the host.
+///
+/// A leading `@` denotes an abstract socket rather than an empty password.
+fn split_password(host: &str) -> (Option<&str>, &str) {
+    match host.split_once('@') {
+        Some((password, host)) if !password.is_empty() => (Some(password), host),
+        _ => (None, host),
+    }
+}
+
+/// Splits the host into its name and optional port.
+///
+/// IPv6 addresses must be wrapped in brackets to include a port.
+/// Returns `None` if the host is malformed.
+fn split_port(host: &str) -> Option<(&str, Option<&str>)> {
+    if let Some(rest) = host.strip_prefix('[') {
+        let (name, rest) = rest.split_once(']')?;
+        return match rest {
+            "" => Some((name, None)),
+            _ => rest.strip_prefix(':').map(|port| (name, Some(port))),
+        };
+    }
+
+    match host.split_once(':') {
+        Some((_, port)) if port.contains(':') => None,
+        Some((name, port)) => Some((name, Some(port))),
+        None => Some((host, None)),
+    }
+}
+
+/// Checks whether the host is a unix socket path or abstract socket name.
+fn is_socket_path(host: &str) -> bool {
+    host.starts_with('/') || host.starts_with('@')
+}
+
+/// Appends the port to the host, adding brackets around IPv6 addresses.
+fn with_port(host: &str, port: &str) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(host: &str, password: Option<&str>) -> Address {
+        Address {
+            host: host.to_string(),
+            password: password.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn from_vars() {
+        assert_eq!(
+            Address::from_vars(None, None, None),
+            address("localhost:6600", None)
+        );
+        assert_eq!(
+            Address::from_vars(Some("music.lan"), Some("6601"), None),
+            address("music.lan:6601", None)
+        );
+        assert_eq!(
+            Address::from_vars(Some("music.lan:6601"), Some("6602"), None),
+            address("music.lan:6601", None)
+        );
+        assert_eq!(
+            Address::from_vars(Some("::1"), None, None),
+            address("[::1]:6600", None)
+        );
+        assert_eq!(
+            Address::from_vars(Some("[::1]:6601"), None, None),
+            address("[::1]:6601", None)
+        );
+        assert_eq!(
+            Address::from_vars(Some("/run/mpd/socket"), Some("6601"), None),
+            address("/run/mpd/socket", None)
+        );
+        assert_eq!(
+            Address::from_vars(Some("@mpd"), None, None),
+            address("@mpd", None)
+        );
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Address::parse("secret@music.lan:6601").unwrap(),
+            address("music.lan:6601", Some("secret"))
+        );
+        assert_eq!(
+            Address::parse("music.lan").unwrap(),
+            address("music.lan:6600", None)
+        );
+        assert_eq!(
+            Address::parse("tls://secret@[::1]:6601").unwrap(),
+            address("tls://[::1]:6601", Some("secret"))
+        );
+        assert_eq!(
+            Address::parse("secret@/run/mpd/socket").unwrap(),
+            address("/run/mpd/socket", Some("secret"))
+        );
+        assert_eq!(Address::parse("@mpd").unwrap(), address("@mpd", None));
+
+        for invalid in ["", "secret@", "music.lan:port", "::1", "[::1", ":6600", "@"] {
+            assert!(
+                matches!(Address::parse(invalid), Err(Error::InvalidAddress(_))),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn from_vars_password() {
+        assert_eq!(
+            Address::from_vars(Some("secret@music.lan"), None, None),
+            address("music.lan:6600", Some("secret"))
+        );
+        assert_eq!(
+            Address::from_vars(Some("secret@@mpd"), None, Some("other")),
+            address("@mpd", Some("secret"))
+        );
+        assert_eq!(
+            Address::from_vars(Some("music.lan"), None, Some("other")),
+            address("music.lan:6600", Some("other"))
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Album art shared between the cache and callers.
+/// `None` records that a song has no art.
+pub(crate) type Art = Option<Arc<[u8]>>;
+
+/// Least recently used cache of album art by song URI,
+/// bounded by the total size of its entries.
+#[derive(Debug)]
+pub(crate) struct ArtCache {
+    /// Maximum total size of the entries, in bytes.
+    capacity: usize,
+    /// Total size of the entries, in bytes.
+    size: usize,
+    /// Entries from least to most recently used.
+    entries: VecDeque<(String, Art)>,
+}
+
+impl ArtCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Gets the art for `uri`, marking it as most recently used.
+    ///
+    /// Returns `None` if there is no entry for `uri`,
+    /// or `Some(None)` if the song is known to have no art.
+    pub(crate) fn get(&mut self, uri: &str) -> Option<Art> {
+        let index = self.entries.iter().position(|(key, _)| key == uri)?;
+        let entry = self.entries.remove(index)?;
+        let art = entry.1.clone();
+
+        self.entries.push_back(entry);
+        Some(art)
+    }
+
+    /// Inserts the art for `uri`,
+    /// evicting the least recently used entries to make room.
+    ///
+    /// Entries larger than the whole cache are not stored.
+    pub(crate) fn insert(&mut self, uri: String, art: Art) {
+        let entry_size = entry_size(&uri, &art);
+        if entry_size > self.capacity {
+            return;
+        }
+
+        if let Some(index) = self.entries.iter().position(|(key, _)| *key == uri) {
+            self.remove(index);
+        }
+
+        while self.size + entry_size > self.capacity {
+            self.remove(0);
+        }
+
+        self.size += entry_size;
+        self.entries.push_back((uri, art));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.size = 0;
+    }
+
+    fn remove(&mut self, index: usize) {
+        if let Some((uri, art)) = self.entries.remove(index) {
+            self.size -= entry_size(&uri, &art);
+        }
+    }
+}
+
+/// Gets the size counted against the cache capacity for an entry.
+/// The URI is included so that songs without art still take up space.
+fn entry_size(uri: &str, art: &Art) -> usize {
+    uri.len() + art.as_ref().map_or(0, |art| art.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = ArtCache::new(12);
+
+        cache.insert("a".to_string(), Some(Arc::from([0; 4].as_slice())));
+        cache.insert("b".to_string(), Some(Arc::from([1; 4].as_slice())));
+        assert!(cache.get("a").is_some());
+
+        cache.insert("c".to_string(), None);
+        // Evicts "b", which was used less recently than "a".
+        cache.insert("d".to_string(), Some(Arc::from([2; 4].as_slice())));
+
+        assert!(cache.get("b").is_none());
+        assert_eq!(cache.get("a").unwrap().as_deref(), Some([0; 4].as_slice()));
+        assert_eq!(cache.get("c"), Some(None));
+        assert!(cache.get("d").is_some());
+
+        cache.insert("e".to_string(), Some(Arc::from([3; 64].as_slice())));
+        assert!(cache.get("e").is_none());
+
+        cache.clear();
+        assert!(cache.get("a").is_none());
+    }
+}
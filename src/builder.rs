@@ -0,0 +1,372 @@
+use crate::address::Address;
+use crate::commands::Password;
+use crate::connector::Connector;
+use crate::multi_host_client::{MultiHostClient, SelectionStrategy};
+use crate::persistent_client::PersistentClient;
+use crate::retry::RetryStrategy;
+use crate::socket::ConnectionOptions;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Builder for configuring a [`PersistentClient`].
+///
+/// Defaults to the localhost TCP address
+/// with a connection retry of 5 seconds.
+#[derive(Debug, Clone)]
+pub struct PersistentClientBuilder {
+    pub(crate) hosts: Vec<String>,
+    pub(crate) retry_strategy: RetryStrategy,
+    pub(crate) connection_options: ConnectionOptions,
+    pub(crate) poll_interval: Option<Duration>,
+    pub(crate) heartbeat_interval: Option<Duration>,
+    pub(crate) idle_timeout: Option<Duration>,
+    pub(crate) strict_controls: bool,
+    pub(crate) password: Option<Password>,
+    pub(crate) connect_timeout: Duration,
+    pub(crate) max_attempts: Option<u32>,
+    pub(crate) event_buffer: usize,
+    pub(crate) art_cache_capacity: Option<usize>,
+    pub(crate) partition: Option<String>,
+    pub(crate) connector: Option<Arc<dyn Connector>>,
+}
+
+impl PersistentClientBuilder {
+    /// Creates a new builder with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new builder using the conventional MPD environment variables.
+    ///
+    /// - `MPD_HOST` sets the host, a unix socket path starting with `/`,
+    ///   or an abstract socket name starting with `@`.
+    ///   It can be prefixed with `password@`. Defaults to `localhost`.
+    /// - `MPD_PORT` sets the TCP port,
+    ///   if one is not already given in `MPD_HOST`. Defaults to `6600`.
+    /// - `MPD_PASSWORD` sets the password,
+    ///   if one is not already given in `MPD_HOST`.
+    ///
+    /// Options set on the builder afterwards, such as [`PersistentClientBuilder::host`],
+    /// take precedence over the environment.
+    pub fn from_env() -> Self {
+        let address = Address::from_env();
+
+        Self {
+            hosts: vec![address.host],
+            password: address.password.map(Password),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the host address or socket path to connect to.
+    pub fn host(mut self, host: String) -> Self {
+        self.hosts = vec![host];
+        self
+    }
+
+    /// Sets several addresses for the same server, in order of preference.
+    ///
+    /// Each connection attempt tries the addresses in order,
+    /// and uses the first which connects.
+    ///
+    /// # Panics
+    ///
+    /// When the client is built, if `hosts` is empty.
+    pub fn hosts(mut self, hosts: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.hosts = hosts.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the interval between connection attempts.
+    pub fn retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_strategy = RetryStrategy::Fixed(retry_interval);
+        self
+    }
+
+    /// Sets the strategy for the delay between connection attempts.
+    pub fn retry_strategy(mut self, retry_strategy: RetryStrategy) -> Self {
+        self.retry_strategy = retry_strategy;
+        self
+    }
+
+    /// Sets the time allowed for each connection attempt,
+    /// including the protocol handshake and authentication.
+    ///
+    /// Attempts which time out are retried like any other failure.
+    /// Defaults to 5 seconds.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// Gives up connecting after `max_attempts` consecutive failed attempts.
+    ///
+    /// Once given up, the client stops for good,
+    /// and waiting for a connection returns [`Error::ConnectionFailed`](crate::Error::ConnectionFailed).
+    /// By default, the client retries forever.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts);
+        self
+    }
+
+    /// Sets the number of connection events buffered for each receiver.
+    ///
+    /// Receivers which fall further behind than this miss the oldest events.
+    /// Defaults to 1024.
+    ///
+    /// # Panics
+    ///
+    /// When the client is built, if `capacity` is zero.
+    pub fn event_buffer(mut self, capacity: usize) -> Self {
+        self.event_buffer = capacity;
+        self
+    }
+
+    /// Caches album art fetched with [`PersistentClient::album_art_cached`],
+    /// keeping up to `capacity` bytes of the most recently used art.
+    ///
+    /// By default, album art is not cached.
+    pub fn art_cache(mut self, capacity: usize) -> Self {
+        self.art_cache_capacity = Some(capacity);
+        self
+    }
+
+    /// Connects to all TCP hosts over TLS using the given config.
+    ///
+    /// Individual hosts can instead be prefixed with `tls://`,
+    /// which uses this config if set, or the default config otherwise.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, config: crate::TlsConfig) -> Self {
+        self.connection_options.tls = Some(config);
+        self
+    }
+
+    /// Sets the partition to switch to after each connection is established.
+    ///
+    /// The server resets each new connection to the default partition,
+    /// so this is re-selected after reconnecting.
+    pub fn partition(mut self, partition: String) -> Self {
+        self.partition = Some(partition);
+        self
+    }
+
+    /// Sets the password to authenticate with after connecting.
+    ///
+    /// If the server rejects the password,
+    /// the connection attempt is treated as failed and retried.
+    pub fn password(mut self, password: String) -> Self {
+        self.password = Some(Password(password));
+        self
+    }
+
+    /// Sets whether `TCP_NODELAY` is enabled on TCP connections.
+    ///
+    /// This has no effect on unix sockets.
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.connection_options.tcp_nodelay = nodelay;
+        self
+    }
+
+    /// Enables TCP keepalive on TCP connections,
+    /// sending probes after the connection has been idle for `time`.
+    ///
+    /// This has no effect on unix sockets.
+    pub fn tcp_keepalive(mut self, time: Duration) -> Self {
+        self.connection_options.tcp_keepalive = Some(time);
+        self
+    }
+
+    /// Sends a `ping` to the server every `interval` while connected,
+    /// to detect connections which were lost without being closed,
+    /// such as after the network drops out.
+    ///
+    /// If a ping fails or gets no response within `interval`,
+    /// the connection is dropped and re-established.
+    /// By default, no pings are sent.
+    pub fn heartbeat(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = Some(interval);
+        self
+    }
+
+    /// Closes the connection once no commands have been sent
+    /// and no events received for `timeout`.
+    ///
+    /// The connection is re-established on demand,
+    /// as soon as a command is sent or a connection is waited for.
+    /// By default, the connection is kept open.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets a hook which receives each raw line sent to and received from the server.
+    ///
+    /// This is intended only for debugging protocol issues.
+    /// Binary responses, such as album art, are passed through as lossy text.
+    ///
+    /// This has no effect on custom connectors.
+    #[cfg(feature = "debug-io")]
+    pub fn io_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(crate::IoDirection, &str) + Send + Sync + 'static,
+    {
+        self.connection_options.io_hook = Some(crate::io_debug::IoHook(Arc::new(hook)));
+        self
+    }
+
+    /// Sets whether playback controls return [`Error::NotPlaying`](crate::Error::NotPlaying)
+    /// when used while the player is stopped.
+    ///
+    /// By default, controls fall back to a sensible action instead.
+    /// See each control for its behaviour when stopped.
+    pub fn strict_controls(mut self, strict: bool) -> Self {
+        self.strict_controls = strict;
+        self
+    }
+
+    /// Sets the connector used to establish each connection,
+    /// in place of the default TCP and unix socket logic.
+    ///
+    /// The TCP socket options set on this builder are not applied to custom connectors.
+    pub fn connector<C: Connector + 'static>(mut self, connector: C) -> Self {
+        self.connector = Some(Arc::new(connector));
+        self
+    }
+
+    /// Detects changes by polling the server status on the given interval,
+    /// instead of relying on the server's `idle` notifications.
+    ///
+    /// Subsystem change events are synthesized from the differences between statuses,
+    /// and `idle` is never sent to the server.
+    /// This is intended for servers with missing or incomplete `idle` support.
+    ///
+    /// Without this, servers which reject `idle` are detected automatically,
+    /// and polled every second from the next connection.
+    ///
+    /// Custom connectors (see [`PersistentClientBuilder::connector`]) still send `idle`,
+    /// so only the polling applies to them.
+    pub fn no_idle(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = Some(poll_interval);
+        self
+    }
+
+    /// Creates the client.
+    /// The client must still be initialised with [`PersistentClient::init`].
+    pub fn build(self) -> PersistentClient {
+        PersistentClient::from_builder(self)
+    }
+}
+
+impl Default for PersistentClientBuilder {
+    fn default() -> Self {
+        Self {
+            hosts: vec!["localhost:6600".to_string()],
+            retry_strategy: RetryStrategy::Fixed(Duration::from_secs(5)),
+            connection_options: ConnectionOptions::default(),
+            poll_interval: None,
+            heartbeat_interval: None,
+            idle_timeout: None,
+            strict_controls: false,
+            password: None,
+            connect_timeout: Duration::from_secs(5),
+            max_attempts: None,
+            event_buffer: 1024,
+            art_cache_capacity: None,
+            partition: None,
+            connector: None,
+        }
+    }
+}
+
+/// Builder for configuring a [`MultiHostClient`].
+///
+/// Each host is configured separately,
+/// so hosts can use different retry intervals, passwords or transports.
+/// Defaults to no hosts, with a connection retry of 5 seconds.
+#[derive(Debug, Clone)]
+pub struct MultiHostClientBuilder {
+    pub(crate) hosts: Vec<(String, HostConfig)>,
+    pub(crate) retry_interval: Duration,
+    pub(crate) selection_strategy: SelectionStrategy,
+    pub(crate) selection_ttl: Option<Duration>,
+}
+
+/// Configuration for a single host of a [`MultiHostClientBuilder`].
+#[derive(Debug, Clone)]
+pub(crate) enum HostConfig {
+    /// Address using the default options.
+    Address(String),
+    /// Fully configured client.
+    Builder(Box<PersistentClientBuilder>),
+}
+
+impl MultiHostClientBuilder {
+    /// Creates a new builder with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a host with the given name and address,
+    /// using the retry interval set on this builder.
+    ///
+    /// The name is used as the label for the host.
+    pub fn add_host(mut self, name: impl Into<String>, address: impl Into<String>) -> Self {
+        self.hosts
+            .push((name.into(), HostConfig::Address(address.into())));
+        self
+    }
+
+    /// Adds a host with the given name,
+    /// configured by its own client builder.
+    ///
+    /// Options on this builder, such as the retry interval, do not apply to the host.
+    pub fn add_host_with(
+        mut self,
+        name: impl Into<String>,
+        builder: PersistentClientBuilder,
+    ) -> Self {
+        self.hosts
+            .push((name.into(), HostConfig::Builder(Box::new(builder))));
+        self
+    }
+
+    /// Sets the interval between connection attempts
+    /// for hosts added with [`MultiHostClientBuilder::add_host`].
+    pub fn retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    /// Sets the strategy used to pick the current client.
+    pub fn selection_strategy(mut self, selection_strategy: SelectionStrategy) -> Self {
+        self.selection_strategy = selection_strategy;
+        self
+    }
+
+    /// Sets the time for which the selected host is reused.
+    /// See [`MultiHostClient::set_selection_ttl`].
+    pub fn selection_ttl(mut self, ttl: Duration) -> Self {
+        self.selection_ttl = Some(ttl);
+        self
+    }
+
+    /// Creates the client.
+    /// The client must still be initialised with [`MultiHostClient::init`].
+    ///
+    /// Returns [`Error::DuplicateHost`](crate::Error::DuplicateHost)
+    /// if two hosts were added with the same name.
+    pub fn build(self) -> crate::Result<MultiHostClient> {
+        MultiHostClient::from_builder(self)
+    }
+}
+
+impl Default for MultiHostClientBuilder {
+    fn default() -> Self {
+        Self {
+            hosts: vec![],
+            retry_interval: Duration::from_secs(5),
+            selection_strategy: SelectionStrategy::default(),
+            selection_ttl: None,
+        }
+    }
+}
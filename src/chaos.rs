@@ -0,0 +1,176 @@
+//! Connector which simulates an unreliable server, for testing.
+
+use crate::connector::Connector;
+use crate::socket::{open_stream, ConnectionOptions, Stream};
+use futures::future::BoxFuture;
+use mpd_client::client::Connection;
+use mpd_client::protocol::MpdProtocolError;
+use mpd_client::Client;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{sleep, Sleep};
+use tracing::debug;
+
+/// Connector which randomly delays or fails connection attempts,
+/// and drops established connections after a set time.
+///
+/// This is intended for testing how an application handles an unreliable server.
+/// Connections are otherwise made the same way as the default connector.
+#[derive(Debug, Clone, Default)]
+pub struct ChaosConnector {
+    failure_rate: f64,
+    max_delay: Duration,
+    drop_after: Option<Duration>,
+    options: ConnectionOptions,
+}
+
+impl ChaosConnector {
+    /// Creates a new connector which behaves normally
+    /// until configured otherwise.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the probability of each connection attempt failing,
+    /// between `0.0` and `1.0`.
+    pub fn failure_rate(mut self, failure_rate: f64) -> Self {
+        self.failure_rate = failure_rate;
+        self
+    }
+
+    /// Delays each connection attempt by a random time up to `max_delay`.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Drops each established connection once it has been open for `drop_after`.
+    pub fn drop_after(mut self, drop_after: Duration) -> Self {
+        self.drop_after = Some(drop_after);
+        self
+    }
+
+    async fn connect_chaos(&self, host: &str) -> Result<Connection, MpdProtocolError> {
+        if !self.max_delay.is_zero() {
+            sleep(self.max_delay.mul_f64(fastrand::f64())).await;
+        }
+
+        if fastrand::f64() < self.failure_rate {
+            debug!("Simulating connection failure to '{host}'");
+            return Err(
+                io::Error::new(io::ErrorKind::ConnectionRefused, "simulated failure").into(),
+            );
+        }
+
+        let stream = open_stream(host, &self.options).await?;
+
+        match self.drop_after {
+            Some(drop_after) => Client::connect(ChaosStream::new(stream, drop_after)).await,
+            None => Client::connect(stream).await,
+        }
+    }
+}
+
+impl Connector for ChaosConnector {
+    fn connect<'a>(&'a self, host: &'a str) -> BoxFuture<'a, Result<Connection, MpdProtocolError>> {
+        Box::pin(self.connect_chaos(host))
+    }
+}
+
+/// Stream which is closed once its timer elapses.
+struct ChaosStream {
+    inner: Box<dyn Stream>,
+    drop_at: Pin<Box<Sleep>>,
+    dropped: bool,
+}
+
+impl ChaosStream {
+    fn new(inner: Box<dyn Stream>, drop_after: Duration) -> Self {
+        Self {
+            inner,
+            drop_at: Box::pin(sleep(drop_after)),
+            dropped: false,
+        }
+    }
+
+    /// Checks whether the stream should be closed,
+    /// registering the timer with the task if not.
+    fn poll_dropped(&mut self, cx: &mut Context<'_>) -> bool {
+        if !self.dropped && self.drop_at.as_mut().poll(cx).is_ready() {
+            debug!("Simulating dropped connection");
+            self.dropped = true;
+        }
+
+        self.dropped
+    }
+}
+
+impl AsyncRead for ChaosStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.poll_dropped(cx) {
+            // Reading nothing signals the end of the stream.
+            return Poll::Ready(Ok(()));
+        }
+
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ChaosStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        if this.poll_dropped(cx) {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+
+        Pin::new(&mut this.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FakeServer;
+    use crate::PersistentClient;
+    use std::sync::atomic::Ordering;
+
+    #[tokio::test]
+    async fn drops_and_reconnects() {
+        let server = FakeServer::bind().await;
+
+        let client = PersistentClient::builder()
+            .host(server.addr())
+            .retry_interval(Duration::from_millis(10))
+            .connector(ChaosConnector::new().drop_after(Duration::from_millis(50)))
+            .build();
+
+        let connections = server.serve();
+        client.init();
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(connections.load(Ordering::SeqCst) > 1);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::error::Result;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use mpd_client::client::ConnectionEvent;
+use mpd_client::commands::Command;
+use mpd_client::responses::{SongInQueue, Status};
+use std::sync::Arc;
+
+/// Common interface to the clients in this crate,
+/// so that code can be written generically over any of them.
+///
+/// Errors from every client are returned as [`Error`](crate::Error),
+/// so command errors from a [`PersistentClient`](crate::PersistentClient)
+/// are wrapped in [`Error::CommandError`](crate::Error::CommandError).
+pub trait MpdClient: Send + Sync {
+    /// Runs the provided command on the MPD server.
+    fn command<'a, C>(&'a self, cmd: C) -> BoxFuture<'a, Result<C::Response>>
+    where
+        C: Command + Send + 'a,
+        C::Response: Send;
+
+    /// Runs the `status` command on the MPD server.
+    fn status(&self) -> BoxFuture<'_, Result<Status>>;
+
+    /// Runs the `currentsong` command on the MPD server.
+    fn current_song(&self) -> BoxFuture<'_, Result<Option<SongInQueue>>>;
+
+    /// Gets whether there is a valid connection to a server.
+    fn is_connected(&self) -> bool;
+
+    /// Creates a stream of events from the MPD server.
+    fn event_stream(&self) -> BoxStream<'static, Arc<ConnectionEvent>>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FakeServer;
+    use crate::{MultiHostClient, PersistentClient};
+    use mpd_client::commands;
+    use mpd_client::responses::PlayState;
+    use std::time::Duration;
+
+    async fn stopped(client: &impl MpdClient) -> bool {
+        client.command(commands::Ping).await.unwrap();
+        client.status().await.unwrap().state == PlayState::Stopped
+    }
+
+    #[tokio::test]
+    async fn generic_over_clients() {
+        let server = FakeServer::bind().await;
+        let addr = server.addr();
+        server.serve();
+
+        let client = PersistentClient::new(addr.clone(), Duration::from_secs(5));
+        client.init();
+        assert!(stopped(&client).await);
+        assert!(MpdClient::is_connected(&client));
+
+        let multi = MultiHostClient::new([addr], Duration::from_secs(5));
+        multi.init();
+        assert!(stopped(&multi).await);
+        assert!(MpdClient::is_connected(&multi));
+    }
+}
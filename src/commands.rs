@@ -0,0 +1,269 @@
+//! Typed commands which are not provided by `mpd_client`.
+//!
+//! These can be sent using [`PersistentClient::command`](crate::PersistentClient::command)
+//! like any other command.
+
+use mpd_client::commands::{self, Command};
+use mpd_client::protocol::command::Command as RawCommand;
+use mpd_client::protocol::response::Frame;
+use mpd_client::responses::{SongInQueue, TypedResponseError};
+use std::fmt::{Debug, Formatter};
+use std::path::PathBuf;
+
+/// A decoder plugin available on the server.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Decoder {
+    /// Name of the plugin.
+    pub plugin: String,
+    /// File suffixes the plugin can decode.
+    pub suffixes: Vec<String>,
+    /// MIME types the plugin can decode.
+    pub mime_types: Vec<String>,
+}
+
+/// `decoders` command.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Decoders;
+
+impl Command for Decoders {
+    type Response = Vec<Decoder>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("decoders")
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        let mut decoders = Vec::<Decoder>::new();
+
+        for (key, value) in frame {
+            if &*key == "plugin" {
+                decoders.push(Decoder {
+                    plugin: value,
+                    ..Decoder::default()
+                });
+
+                continue;
+            }
+
+            match (&*key, decoders.last_mut()) {
+                ("suffix", Some(decoder)) => decoder.suffixes.push(value),
+                ("mime_type", Some(decoder)) => decoder.mime_types.push(value),
+                _ => return Err(TypedResponseError::unexpected_field("plugin", &*key)),
+            }
+        }
+
+        Ok(decoders)
+    }
+}
+
+/// `plchanges` command.
+///
+/// Returns the songs in the queue which changed since the given queue version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueChanges(pub u32);
+
+impl Command for QueueChanges {
+    type Response = Vec<SongInQueue>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("plchanges").argument(self.0)
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        // The response uses the same format as `playlistinfo`.
+        commands::Queue.response(frame)
+    }
+}
+
+/// `config` command.
+///
+/// Returns the music directory configured on the server, if set.
+/// The server only allows this command over local (unix socket) connections.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Config;
+
+impl Command for Config {
+    type Response = Option<PathBuf>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("config")
+    }
+
+    fn response(self, mut frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(frame.get("music_directory").map(PathBuf::from))
+    }
+}
+
+/// `add` command.
+///
+/// Unlike [`commands::Add`], this accepts directories,
+/// which are added recursively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddUri<'a>(pub &'a str);
+
+impl<'a> Command for AddUri<'a> {
+    type Response = ();
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("add").argument(self.0)
+    }
+
+    fn response(self, _frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(())
+    }
+}
+
+/// `status` command, reading only the volume.
+///
+/// Unlike [`commands::Status`], this returns `None`
+/// when the server has no mixer, rather than a volume of `0`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Volume;
+
+impl Command for Volume {
+    type Response = Option<u8>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("status")
+    }
+
+    fn response(self, mut frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        match frame.get("volume") {
+            // Servers without a mixer report `-1` or omit the field.
+            Some(volume) => match volume.parse::<i16>() {
+                Ok(volume) => Ok(u8::try_from(volume).ok()),
+                Err(_) => Err(TypedResponseError::invalid_value("volume", volume)),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+/// `status` command, reading only the partition.
+///
+/// Returns `None` for servers without partition support,
+/// which do not report the field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CurrentPartition;
+
+impl Command for CurrentPartition {
+    type Response = Option<String>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("status")
+    }
+
+    fn response(self, mut frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(frame.get("partition"))
+    }
+}
+
+/// `partition` command.
+///
+/// Switches the connection to the partition with the given name.
+/// This requires MPD 0.22 or newer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Partition<'a>(pub &'a str);
+
+impl<'a> Command for Partition<'a> {
+    type Response = ();
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("partition").argument(self.0)
+    }
+
+    fn response(self, _frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(())
+    }
+}
+
+/// `readcomments` command.
+///
+/// Returns all metadata comments embedded in the file at the given URI,
+/// including those which the server does not index as tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadComments<'a>(pub &'a str);
+
+impl<'a> Command for ReadComments<'a> {
+    type Response = Vec<(String, String)>;
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("readcomments").argument(self.0)
+    }
+
+    fn response(self, frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(frame
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect())
+    }
+}
+
+/// `outputset` command.
+///
+/// Sets a runtime attribute on the output with the given id.
+/// This requires MPD 0.21 or newer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputSet<'a> {
+    pub id: u32,
+    pub name: &'a str,
+    pub value: &'a str,
+}
+
+impl<'a> Command for OutputSet<'a> {
+    type Response = ();
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("outputset")
+            .argument(self.id)
+            .argument(self.name)
+            .argument(self.value)
+    }
+
+    fn response(self, _frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(())
+    }
+}
+
+/// `clearerror` command.
+///
+/// Clears the current playback error from the server status.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClearError;
+
+impl Command for ClearError {
+    type Response = ();
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("clearerror")
+    }
+
+    fn response(self, _frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(())
+    }
+}
+
+/// `password` command.
+///
+/// Authenticates the connection with the given password.
+/// The password is not included in the debug output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Password(pub String);
+
+impl Debug for Password {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Password(..)")
+    }
+}
+
+impl Command for Password {
+    type Response = ();
+
+    fn command(&self) -> RawCommand {
+        RawCommand::new("password").argument(self.0.as_str())
+    }
+
+    fn response(self, _frame: Frame) -> Result<Self::Response, TypedResponseError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+use crate::no_idle::IdleHold;
+use crate::socket::{try_get_connection, ConnectionOptions};
+use futures::future::BoxFuture;
+use mpd_client::client::Connection;
+use mpd_client::protocol::MpdProtocolError;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Establishes connections to a host.
+///
+/// [`PersistentClient`](crate::PersistentClient) uses this for every connection attempt,
+/// so a custom implementation can be provided with
+/// [`PersistentClientBuilder::connector`](crate::PersistentClientBuilder::connector)
+/// to use an alternative transport, or to simulate failures in tests.
+pub trait Connector: Debug + Send + Sync {
+    /// Connects to the given host and starts the MPD protocol.
+    fn connect<'a>(&'a self, host: &'a str) -> BoxFuture<'a, Result<Connection, MpdProtocolError>>;
+}
+
+/// Default connector, which connects over TCP
+/// or a unix socket if the host is a socket path.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SocketConnector {
+    pub(crate) options: ConnectionOptions,
+    /// Whether to connect without `idle`,
+    /// shared with the client so that it can be set once `idle` is found to be unsupported.
+    pub(crate) no_idle: Arc<AtomicBool>,
+    /// Holds off `idle` during command sessions, shared with the client.
+    pub(crate) idle_hold: Arc<IdleHold>,
+}
+
+impl Connector for SocketConnector {
+    fn connect<'a>(&'a self, host: &'a str) -> BoxFuture<'a, Result<Connection, MpdProtocolError>> {
+        Box::pin(try_get_connection(
+            host,
+            &self.options,
+            self.no_idle.load(Ordering::Relaxed),
+            &self.idle_hold,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FakeServer;
+    use crate::PersistentClientBuilder;
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Connector which fails a number of attempts before connecting normally.
+    #[derive(Debug, Default)]
+    struct FlakyConnector {
+        failures: usize,
+        attempts: AtomicUsize,
+        inner: SocketConnector,
+    }
+
+    impl Connector for FlakyConnector {
+        fn connect<'a>(
+            &'a self,
+            host: &'a str,
+        ) -> BoxFuture<'a, Result<Connection, MpdProtocolError>> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Box::pin(async {
+                    Err(MpdProtocolError::Io(io::Error::other("simulated failure")))
+                });
+            }
+
+            self.inner.connect(host)
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_connector_is_used() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClientBuilder::new()
+            .host(server.addr())
+            .retry_interval(Duration::from_millis(10))
+            .connector(FlakyConnector {
+                failures: 2,
+                ..FlakyConnector::default()
+            })
+            .build();
+
+        let connections = server.serve();
+        client.init();
+
+        tokio::time::timeout(Duration::from_secs(5), client.wait_for_client())
+            .await
+            .expect("client should connect")
+            .unwrap();
+
+        assert_eq!(client.stats().failed_attempts, 2);
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        client.shutdown();
+    }
+}
@@ -1,10 +1,39 @@
 use mpd_client::client::CommandError;
+use mpd_client::protocol::MpdProtocolError;
 use std::fmt::{Display, Formatter};
 
 #[derive(Debug)]
 pub enum Error {
     NoHostConnectedError,
+    /// No host of a [`MultiHostClient`](crate::MultiHostClient)
+    /// has the given label or index.
+    UnknownHost(String),
+    /// A host was added to a [`MultiHostClient`](crate::MultiHostClient)
+    /// with the same label as another host.
+    DuplicateHost(String),
     CommandError(CommandError),
+    /// A command failed on a specific host of a
+    /// [`MultiHostClient`](crate::MultiHostClient).
+    HostCommandError {
+        /// Label of the host the command was sent to.
+        host: String,
+        error: CommandError,
+    },
+    /// The connection to the server failed at the protocol level,
+    /// such as while connecting.
+    ProtocolError(MpdProtocolError),
+    Timeout,
+    /// A playback control was used while the player is stopped.
+    /// Seeking always returns this when stopped,
+    /// and other controls only if the client has strict controls enabled.
+    NotPlaying,
+    /// The client is not currently connected to the server.
+    NotConnected,
+    /// A connection string could not be parsed.
+    InvalidAddress(String),
+    /// The client gave up connecting after reaching its maximum number of attempts.
+    /// Contains the error from the last attempt.
+    ConnectionFailed(String),
 }
 
 impl Display for Error {
@@ -14,12 +43,100 @@ impl Display for Error {
             "{}",
             match self {
                 Error::NoHostConnectedError => "No host connected".to_string(),
+                Error::UnknownHost(host) => format!("Unknown host '{host}'"),
+                Error::DuplicateHost(host) => format!("Duplicate host '{host}'"),
                 Error::CommandError(err) => err.to_string(),
+                Error::HostCommandError { host, error } => {
+                    format!("Command failed on '{host}': {error}")
+                }
+                Error::ProtocolError(err) => err.to_string(),
+                Error::Timeout => "Timed out".to_string(),
+                Error::NotPlaying => "Nothing is playing".to_string(),
+                Error::NotConnected => "Not connected".to_string(),
+                Error::InvalidAddress(reason) => format!("Invalid address: {reason}"),
+                Error::ConnectionFailed(err) => format!("Failed to connect: {err}"),
             }
         )
     }
 }
 
+impl Error {
+    /// Gets the underlying command error, if the error came from a command.
+    pub fn command_error(&self) -> Option<&CommandError> {
+        match self {
+            Error::CommandError(err) | Error::HostCommandError { error: err, .. } => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Gets the label of the host the error occurred on, if known.
+    pub fn host(&self) -> Option<&str> {
+        match self {
+            Error::HostCommandError { host, .. } => Some(host),
+            _ => None,
+        }
+    }
+
+    /// Attaches the host to a command error.
+    /// Other errors are returned unchanged.
+    pub(crate) fn with_host(self, host: &str) -> Self {
+        match self {
+            Error::CommandError(error) => Error::HostCommandError {
+                host: host.to_string(),
+                error,
+            },
+            err => err,
+        }
+    }
+}
+
 impl std::error::Error for Error {}
 
+impl From<CommandError> for Error {
+    fn from(err: CommandError) -> Self {
+        Error::CommandError(err)
+    }
+}
+
+impl From<MpdProtocolError> for Error {
+    fn from(err: MpdProtocolError) -> Self {
+        Error::ProtocolError(err)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_inner_errors() {
+        fn command() -> Result<()> {
+            Err(CommandError::ConnectionClosed)?
+        }
+
+        fn protocol() -> Result<()> {
+            Err(MpdProtocolError::Io(std::io::Error::other("reset")))?
+        }
+
+        assert!(matches!(command(), Err(Error::CommandError(_))));
+        assert!(matches!(protocol(), Err(Error::ProtocolError(_))));
+    }
+
+    #[test]
+    fn with_host_context() {
+        let err = Error::CommandError(CommandError::ConnectionClosed).with_host("bedroom:6600");
+
+        assert_eq!(err.host(), Some("bedroom:6600"));
+        assert!(matches!(
+            err.command_error(),
+            Some(CommandError::ConnectionClosed)
+        ));
+        assert!(err
+            .to_string()
+            .starts_with("Command failed on 'bedroom:6600': "));
+
+        assert!(Error::Timeout.with_host("bedroom:6600").host().is_none());
+    }
+}
@@ -0,0 +1,149 @@
+//! Hook for observing the raw protocol traffic, for debugging.
+
+use crate::socket::Stream;
+use std::fmt::{Debug, Formatter};
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Direction of a raw protocol line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoDirection {
+    /// Sent from the client to the server.
+    Sent,
+    /// Received by the client from the server.
+    Received,
+}
+
+type HookFn = dyn Fn(IoDirection, &str) + Send + Sync;
+
+/// Callback receiving each raw line sent to and received from the server.
+#[derive(Clone)]
+pub(crate) struct IoHook(pub(crate) Arc<HookFn>);
+
+impl Debug for IoHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("IoHook")
+    }
+}
+
+/// Stream which passes each complete line to the hook.
+pub(crate) struct DebugStream {
+    inner: Box<dyn Stream>,
+    hook: IoHook,
+    read_buf: Vec<u8>,
+    write_buf: Vec<u8>,
+}
+
+impl DebugStream {
+    pub(crate) fn new(inner: Box<dyn Stream>, hook: IoHook) -> Self {
+        Self {
+            inner,
+            hook,
+            read_buf: vec![],
+            write_buf: vec![],
+        }
+    }
+}
+
+/// Appends `data` to `buf`, and passes each completed line to the hook.
+fn emit_lines(hook: &IoHook, direction: IoDirection, buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend_from_slice(data);
+
+    while let Some(end) = buf.iter().position(|&byte| byte == b'\n') {
+        let line = buf.drain(..=end).collect::<Vec<_>>();
+        (hook.0)(direction, &String::from_utf8_lossy(&line[..end]));
+    }
+}
+
+impl AsyncRead for DebugStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let start = buf.filled().len();
+
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = res {
+            emit_lines(
+                &this.hook,
+                IoDirection::Received,
+                &mut this.read_buf,
+                &buf.filled()[start..],
+            );
+        }
+
+        res
+    }
+}
+
+impl AsyncWrite for DebugStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        let res = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = res {
+            emit_lines(
+                &this.hook,
+                IoDirection::Sent,
+                &mut this.write_buf,
+                &buf[..written],
+            );
+        }
+
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn emit_lines_split() {
+        let lines = Arc::new(Mutex::new(vec![]));
+
+        let hook = {
+            let lines = lines.clone();
+            IoHook(Arc::new(move |direction, line: &str| {
+                lines.lock().unwrap().push((direction, line.to_string()));
+            }))
+        };
+
+        let mut buf = vec![];
+        emit_lines(
+            &hook,
+            IoDirection::Received,
+            &mut buf,
+            b"OK MPD 0.23.5\nvol",
+        );
+        emit_lines(&hook, IoDirection::Received, &mut buf, b"ume: 50\nOK\n");
+
+        assert!(buf.is_empty());
+        assert_eq!(
+            *lines.lock().unwrap(),
+            vec![
+                (IoDirection::Received, "OK MPD 0.23.5".to_string()),
+                (IoDirection::Received, "volume: 50".to_string()),
+                (IoDirection::Received, "OK".to_string()),
+            ]
+        );
+    }
+}
@@ -1,9 +1,48 @@
+mod address;
+mod art_cache;
+mod builder;
+#[cfg(feature = "chaos")]
+mod chaos;
+mod client;
+pub mod commands;
+mod connector;
 mod error;
+#[cfg(feature = "debug-io")]
+mod io_debug;
+#[cfg(feature = "mock")]
+mod mock;
 mod multi_host_client;
+mod no_idle;
 mod persistent_client;
+mod player;
+mod retry;
+mod session;
 mod socket;
+mod stats;
+#[cfg(test)]
+mod test_utils;
+#[cfg(feature = "tls")]
+mod tls;
 
-pub use multi_host_client::MultiHostClient;
-pub use persistent_client::PersistentClient;
+pub use builder::{MultiHostClientBuilder, PersistentClientBuilder};
+#[cfg(feature = "chaos")]
+pub use chaos::ChaosConnector;
+pub use client::MpdClient;
+pub use connector::Connector;
+pub use error::{Error, Result};
+#[cfg(feature = "debug-io")]
+pub use io_debug::IoDirection;
+#[cfg(feature = "mock")]
+pub use mock::MockClient;
+pub use multi_host_client::{HostEvent, MultiHostClient, SelectionStrategy};
+pub use persistent_client::{ConnectionState, PersistentClient};
+pub use player::{NowPlaying, PlaybackOptions, PlayerEvent};
+pub use retry::{BackoffConfig, RetryStrategy};
+pub use session::CommandSession;
+pub use stats::ClientStats;
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
 
 pub use mpd_client;
+#[cfg(feature = "tls")]
+pub use tokio_rustls::rustls;
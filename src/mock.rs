@@ -0,0 +1,438 @@
+//! In-memory client with canned responses, for testing.
+
+use crate::builder::PersistentClientBuilder;
+use crate::client::MpdClient;
+use crate::connector::Connector;
+use crate::persistent_client::PersistentClient;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use mpd_client::client::{CommandError, Connection, ConnectionEvent, Subsystem};
+use mpd_client::commands::Command;
+use mpd_client::protocol::MpdProtocolError;
+use mpd_client::responses::{SongInQueue, Status};
+use mpd_client::Client;
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::spawn;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::debug;
+
+/// Response sent for `status` unless another is set,
+/// describing a stopped player with an empty queue.
+const DEFAULT_STATUS: &str = "repeat: 0\nrandom: 0\nsingle: 0\nconsume: 0\nstate: stop\n";
+
+/// Client backed by an in-memory server,
+/// for testing application logic without a running MPD server.
+///
+/// Responses to each command are set with [`MockClient::set_response`],
+/// and server-side changes are simulated with [`MockClient::notify`]
+/// and [`MockClient::disconnect`].
+///
+/// The mock uses a real [`PersistentClient`] internally,
+/// so responses are parsed and events are delivered exactly as they would be from a server.
+///
+/// Cloning the client is cheap, and all clones share the same server.
+#[derive(Debug, Clone)]
+pub struct MockClient {
+    server: Arc<MockServer>,
+    client: PersistentClient,
+}
+
+/// Response to a command from the mock server.
+#[derive(Debug, Clone)]
+enum MockResponse {
+    /// Response body, without the trailing `OK`.
+    Ok(String),
+    Error {
+        code: u64,
+        message: String,
+    },
+}
+
+/// Instruction sent to each open connection.
+#[derive(Debug, Clone)]
+enum Control {
+    Changed(String),
+    Disconnect,
+}
+
+#[derive(Debug)]
+struct MockServer {
+    /// Responses by command name.
+    responses: Mutex<HashMap<String, MockResponse>>,
+    /// Whether new connections are accepted.
+    online: AtomicBool,
+    control: broadcast::Sender<Control>,
+}
+
+impl MockClient {
+    /// Creates and initialises a new client,
+    /// which connects to its in-memory server straight away.
+    ///
+    /// This must be called from within a tokio runtime.
+    pub fn new() -> Self {
+        let server = Arc::new(MockServer {
+            responses: Mutex::default(),
+            online: AtomicBool::new(true),
+            control: broadcast::channel(64).0,
+        });
+
+        let client = PersistentClientBuilder::new()
+            .host("mock".to_string())
+            .retry_interval(Duration::from_millis(10))
+            .connector(MockConnector {
+                server: server.clone(),
+            })
+            .build();
+        client.init();
+
+        Self { server, client }
+    }
+
+    /// Gets the underlying client,
+    /// for using any method not provided directly on the mock.
+    pub fn client(&self) -> &PersistentClient {
+        &self.client
+    }
+
+    /// Sets the response body sent for the command `command`,
+    /// as the raw protocol lines without the trailing `OK`.
+    ///
+    /// Commands without a response set reply with an empty body,
+    /// except for `status`, which describes a stopped player.
+    pub fn set_response(&self, command: &str, response: &str) {
+        let mut response = response.to_string();
+        if !response.is_empty() && !response.ends_with('\n') {
+            response.push('\n');
+        }
+
+        self.server
+            .responses
+            .lock()
+            .expect("Failed to get lock on responses")
+            .insert(command.to_string(), MockResponse::Ok(response));
+    }
+
+    /// Sets the command `command` to fail with the given error code and message.
+    pub fn set_error(&self, command: &str, code: u64, message: &str) {
+        self.server
+            .responses
+            .lock()
+            .expect("Failed to get lock on responses")
+            .insert(
+                command.to_string(),
+                MockResponse::Error {
+                    code,
+                    message: message.to_string(),
+                },
+            );
+    }
+
+    /// Simulates a change to `subsystem` on the server,
+    /// such as [`Subsystem::Player`] after the song changes.
+    pub fn notify(&self, subsystem: Subsystem) {
+        // Sending only fails when there are no connections, which is fine.
+        let _ = self
+            .server
+            .control
+            .send(Control::Changed(subsystem.as_str().to_string()));
+    }
+
+    /// Closes the connection to the server,
+    /// and refuses further connection attempts until [`MockClient::restore`] is called.
+    pub fn disconnect(&self) {
+        self.server.online.store(false, Ordering::SeqCst);
+        let _ = self.server.control.send(Control::Disconnect);
+    }
+
+    /// Accepts connections to the server again after [`MockClient::disconnect`].
+    pub fn restore(&self) {
+        self.server.online.store(true, Ordering::SeqCst);
+        self.client.reconnect();
+    }
+
+    /// Gets whether there is a valid connection to the server.
+    pub fn is_connected(&self) -> bool {
+        self.client.is_connected()
+    }
+
+    /// Waits for a valid connection to the server to be established.
+    /// See [`PersistentClient::wait_for_client`].
+    pub async fn wait_for_client(&self) -> crate::Result<Arc<Client>> {
+        self.client.wait_for_client().await
+    }
+
+    /// Receives the next event from the server.
+    /// See [`PersistentClient::recv`].
+    pub async fn recv(&mut self) -> Option<Arc<ConnectionEvent>> {
+        self.client.recv().await
+    }
+
+    /// Creates a new receiver for events from the server.
+    /// See [`PersistentClient::subscribe`].
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<ConnectionEvent>> {
+        self.client.subscribe()
+    }
+
+    /// Runs the provided command against the mock server.
+    /// See [`PersistentClient::command`].
+    pub async fn command<C: Command>(&self, cmd: C) -> Result<C::Response, CommandError> {
+        self.client.command(cmd).await
+    }
+
+    /// Runs the `status` command against the mock server.
+    pub async fn status(&self) -> Result<Status, CommandError> {
+        self.client.status().await
+    }
+
+    /// Runs the `currentsong` command against the mock server.
+    pub async fn current_song(&self) -> Result<Option<SongInQueue>, CommandError> {
+        self.client.current_song().await
+    }
+}
+
+impl MpdClient for MockClient {
+    fn command<'a, C>(&'a self, cmd: C) -> BoxFuture<'a, crate::Result<C::Response>>
+    where
+        C: Command + Send + 'a,
+        C::Response: Send,
+    {
+        MpdClient::command(&self.client, cmd)
+    }
+
+    fn status(&self) -> BoxFuture<'_, crate::Result<Status>> {
+        MpdClient::status(&self.client)
+    }
+
+    fn current_song(&self) -> BoxFuture<'_, crate::Result<Option<SongInQueue>>> {
+        MpdClient::current_song(&self.client)
+    }
+
+    fn is_connected(&self) -> bool {
+        self.client.is_connected()
+    }
+
+    fn event_stream(&self) -> BoxStream<'static, Arc<ConnectionEvent>> {
+        MpdClient::event_stream(&self.client)
+    }
+}
+
+impl Default for MockClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockServer {
+    /// Gets the response to a single command line, including the trailing `OK`.
+    fn response(&self, line: &str) -> String {
+        self.list_response(&[line.to_string()], false)
+    }
+
+    /// Gets the response to a list of command lines.
+    ///
+    /// As with a real server, the list stops at the first error.
+    fn list_response(&self, lines: &[String], list_ok: bool) -> String {
+        let responses = self
+            .responses
+            .lock()
+            .expect("Failed to get lock on responses");
+
+        let mut out = String::new();
+
+        for (index, line) in lines.iter().enumerate() {
+            let command = line.split_whitespace().next().unwrap_or_default();
+
+            match responses.get(command) {
+                Some(MockResponse::Ok(body)) => out.push_str(body),
+                Some(MockResponse::Error { code, message }) => {
+                    out.push_str(&format!("ACK [{code}@{index}] {{{command}}} {message}\n"));
+                    return out;
+                }
+                None if command == "status" => out.push_str(DEFAULT_STATUS),
+                None => {}
+            }
+
+            if list_ok {
+                out.push_str("list_OK\n");
+            }
+        }
+
+        out.push_str("OK\n");
+        out
+    }
+}
+
+/// Connector which opens an in-memory connection to the mock server.
+#[derive(Debug)]
+struct MockConnector {
+    server: Arc<MockServer>,
+}
+
+impl Connector for MockConnector {
+    fn connect<'a>(
+        &'a self,
+        _host: &'a str,
+    ) -> BoxFuture<'a, Result<Connection, MpdProtocolError>> {
+        Box::pin(async move {
+            if !self.server.online.load(Ordering::SeqCst) {
+                return Err(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    "mock server is offline",
+                )
+                .into());
+            }
+
+            let (client, server) = tokio::io::duplex(64 * 1024);
+
+            // Subscribed before spawning, so that a disconnect is not missed.
+            let control = self.server.control.subscribe();
+            spawn(serve(server, self.server.clone(), control));
+
+            Client::connect(client).await
+        })
+    }
+}
+
+/// Answers commands on a single connection until it is closed,
+/// or the server disconnects it.
+async fn serve(
+    stream: DuplexStream,
+    server: Arc<MockServer>,
+    mut control: broadcast::Receiver<Control>,
+) {
+    let (read, mut write) = tokio::io::split(stream);
+
+    if write.write_all(b"OK MPD 0.23.5\n").await.is_err() {
+        return;
+    }
+
+    let mut lines = BufReader::new(read).lines();
+    let mut idling = false;
+    let mut changed = Vec::new();
+    let mut list: Option<Vec<String>> = None;
+
+    loop {
+        let response = tokio::select! {
+            line = lines.next_line() => {
+                let Ok(Some(line)) = line else {
+                    return;
+                };
+
+                match (line.as_str(), &mut list) {
+                    ("command_list_ok_begin", _) => {
+                        list = Some(vec![]);
+                        continue;
+                    }
+                    ("command_list_end", _) => {
+                        server.list_response(&list.take().unwrap_or_default(), true)
+                    }
+                    (_, Some(list)) => {
+                        list.push(line);
+                        continue;
+                    }
+                    ("idle", None) if changed.is_empty() => {
+                        idling = true;
+                        continue;
+                    }
+                    ("idle" | "noidle", None) => {
+                        idling = false;
+                        changes(&mut changed)
+                    }
+                    (_, None) => server.response(&line),
+                }
+            }
+            control = control.recv() => match control {
+                Ok(Control::Changed(subsystem)) => {
+                    if !changed.contains(&subsystem) {
+                        changed.push(subsystem);
+                    }
+
+                    if !idling {
+                        continue;
+                    }
+
+                    idling = false;
+                    changes(&mut changed)
+                }
+                Ok(Control::Disconnect) | Err(RecvError::Closed) => {
+                    debug!("Mock server closing connection");
+                    return;
+                }
+                Err(RecvError::Lagged(_)) => continue,
+            }
+        };
+
+        if write.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Gets the response to `idle` for the pending changes, clearing them.
+fn changes(changed: &mut Vec<String>) -> String {
+    let mut out = changed
+        .drain(..)
+        .map(|subsystem| format!("changed: {subsystem}\n"))
+        .collect::<String>();
+
+    out.push_str("OK\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mpd_client::commands;
+
+    #[tokio::test]
+    async fn canned_responses_and_events() {
+        let mock = MockClient::new();
+        let mut events = mock.subscribe();
+
+        assert!(mock.current_song().await.unwrap().is_none());
+
+        mock.set_response("currentsong", "file: song.flac\nPos: 0\nId: 1");
+        mock.notify(Subsystem::Player);
+
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("event should be received")
+            .unwrap();
+        assert!(matches!(
+            &*event,
+            ConnectionEvent::SubsystemChange(Subsystem::Player)
+        ));
+
+        let song = mock.current_song().await.unwrap().unwrap();
+        assert_eq!(song.song.url, "song.flac");
+
+        mock.set_error("next", 2, "Not playing");
+        assert!(matches!(
+            mock.command(commands::Next).await,
+            Err(CommandError::ErrorResponse { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn disconnect_and_restore() {
+        let mock = MockClient::new();
+        mock.wait_for_client().await.unwrap();
+
+        mock.disconnect();
+        tokio::time::timeout(Duration::from_secs(5), mock.client().wait_for_disconnect())
+            .await
+            .expect("client should disconnect");
+
+        mock.restore();
+        tokio::time::timeout(Duration::from_secs(5), mock.wait_for_client())
+            .await
+            .expect("client should reconnect")
+            .unwrap();
+    }
+}
@@ -1,159 +1,1298 @@
+use crate::builder::{HostConfig, MultiHostClientBuilder, PersistentClientBuilder};
+use crate::client::MpdClient;
 use crate::error::{Error, Result};
 use crate::persistent_client::PersistentClient;
-use mpd_client::client::{CommandError, ConnectionEvent};
+use crate::player::NowPlaying;
+use crate::retry::RetryStrategy;
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use mpd_client::client::ConnectionEvent;
+use mpd_client::commands::{self, Command};
 use mpd_client::responses::{PlayState, SongInQueue, Status};
 use mpd_client::Client;
+use std::collections::HashSet;
 use std::future::Future;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{timeout_at, Instant};
+
+/// Default time for which the selected host is reused.
+const DEFAULT_SELECTION_TTL: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+struct HostEntry {
+    /// Label used to refer to the host.
+    /// This is the host name if one was given, otherwise the host address.
+    label: String,
+    client: PersistentClient,
+    /// Whether the host is considered by `get_current_host`.
+    selectable: AtomicBool,
+}
+
+impl HostEntry {
+    fn new(label: String, client: PersistentClient) -> Self {
+        Self {
+            label,
+            client,
+            selectable: AtomicBool::new(true),
+        }
+    }
+
+    fn is_selectable(&self) -> bool {
+        self.selectable.load(Ordering::Relaxed)
+    }
+
+    /// Converts the error, attaching this host to command errors.
+    fn error(&self, err: impl Into<Error>) -> Error {
+        err.into().with_host(&self.label)
+    }
+}
+
+/// Event received from one of the hosts of a [`MultiHostClient`].
+#[derive(Debug, Clone)]
+pub struct HostEvent {
+    /// Label of the host which produced the event.
+    pub host: String,
+    pub event: Arc<ConnectionEvent>,
+}
+
+/// Strategy used to pick the current client
+/// from the connected, selectable hosts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    /// Prefer a playing host, then a paused host, then any connected host.
+    ///
+    /// This queries the status of every connected host on each selection.
+    #[default]
+    PlayingFirst,
+    /// Use the first connected host, in the order hosts were added.
+    ///
+    /// This does not send any commands to select a host.
+    FirstConnected,
+    /// Keep using the last selected host for as long as it stays connected,
+    /// regardless of the selection TTL,
+    /// falling back to [`SelectionStrategy::PlayingFirst`] otherwise.
+    Sticky,
+}
 
 pub struct MultiHostClient {
-    clients: Vec<PersistentClient>,
+    hosts: Vec<HostEntry>,
+    selection_strategy: SelectionStrategy,
+    /// Time for which the selected host is reused.
+    selection_ttl: Duration,
+    /// Label of the last selected host, and when it was selected.
+    selected: Mutex<Option<(String, Instant)>>,
 }
 
 impl MultiHostClient {
-    pub fn new(hosts: Vec<String>, retry_interval: Duration) -> Self {
+    pub fn new(
+        hosts: impl IntoIterator<Item = impl Into<String>>,
+        retry_interval: Duration,
+    ) -> Self {
+        Self::with_selection_strategy(hosts, retry_interval, SelectionStrategy::default())
+    }
+
+    /// Creates a client for each host,
+    /// using the given strategy to pick the current client.
+    pub fn with_selection_strategy(
+        hosts: impl IntoIterator<Item = impl Into<String>>,
+        retry_interval: Duration,
+        selection_strategy: SelectionStrategy,
+    ) -> Self {
+        let hosts = hosts
+            .into_iter()
+            .map(Into::into)
+            .map(|host| HostEntry::new(host.clone(), PersistentClient::new(host, retry_interval)))
+            .collect();
+
+        Self::from_hosts(hosts, selection_strategy)
+    }
+
+    /// Creates a client for each `(name, address)` pair.
+    ///
+    /// Hosts are referred to by name rather than address
+    /// wherever a label is used, such as in [`MultiHostClient::command_all`].
+    ///
+    /// Returns [`Error::DuplicateHost`] if two hosts have the same name.
+    pub fn with_named_hosts(hosts: &[(&str, &str)], retry_interval: Duration) -> Result<Self> {
+        check_unique_labels(hosts.iter().map(|&(name, _)| name))?;
+
         let hosts = hosts
+            .iter()
+            .map(|&(name, address)| {
+                HostEntry::new(
+                    name.to_string(),
+                    PersistentClient::new(address.to_string(), retry_interval),
+                )
+            })
+            .collect();
+
+        Ok(Self::from_hosts(hosts, SelectionStrategy::default()))
+    }
+
+    /// Creates a builder for configuring a new client.
+    pub fn builder() -> MultiHostClientBuilder {
+        MultiHostClientBuilder::new()
+    }
+
+    pub(crate) fn from_builder(builder: MultiHostClientBuilder) -> Result<Self> {
+        check_unique_labels(builder.hosts.iter().map(|(label, _)| label.as_str()))?;
+
+        let retry_interval = builder.retry_interval;
+
+        let hosts = builder
+            .hosts
             .into_iter()
-            .map(|host| PersistentClient::new(host, retry_interval))
+            .map(|(label, config)| {
+                let client = match config {
+                    HostConfig::Address(address) => PersistentClientBuilder::new()
+                        .host(address)
+                        .retry_interval(retry_interval)
+                        .build(),
+                    HostConfig::Builder(builder) => builder.build(),
+                };
+
+                HostEntry::new(label, client)
+            })
             .collect();
 
-        Self { clients: hosts }
+        let mut client = Self::from_hosts(hosts, builder.selection_strategy);
+        if let Some(ttl) = builder.selection_ttl {
+            client.set_selection_ttl(ttl);
+        }
+
+        Ok(client)
+    }
+
+    fn from_hosts(hosts: Vec<HostEntry>, selection_strategy: SelectionStrategy) -> Self {
+        Self {
+            hosts,
+            selection_strategy,
+            selection_ttl: DEFAULT_SELECTION_TTL,
+            selected: Mutex::new(None),
+        }
+    }
+
+    fn clients(&self) -> impl Iterator<Item = &PersistentClient> {
+        self.hosts.iter().map(|host| &host.client)
     }
 
     /// Initialises each of the clients.
     pub fn init(&self) {
-        for client in &self.clients {
+        for client in self.clients() {
             client.init();
         }
     }
 
+    /// Creates and initialises a client for a new host.
+    /// The host address is used as its label.
+    ///
+    /// The host takes part in selecting the current client
+    /// as soon as it connects.
+    ///
+    /// Returns [`Error::DuplicateHost`] if a host with the same label already exists,
+    /// leaving the existing host in place.
+    pub fn add_host(&mut self, host: String, retry_interval: Duration) -> Result<()> {
+        if self.hosts.iter().any(|entry| entry.label == host) {
+            return Err(Error::DuplicateHost(host));
+        }
+
+        let client = PersistentClient::new(host.clone(), retry_interval);
+        client.init();
+
+        self.hosts.push(HostEntry::new(host, client));
+        Ok(())
+    }
+
+    /// Shuts down and removes the client for the host with the given label.
+    ///
+    /// Returns `false` if no host has the given label.
+    pub fn remove_host(&mut self, label: &str) -> bool {
+        match self.hosts.iter().position(|host| host.label == label) {
+            Some(index) => {
+                self.hosts.remove(index).client.shutdown();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Gets the strategy for the delay between connection attempts
+    /// for each host by label.
+    pub fn retry_configs(&self) -> Vec<(String, RetryStrategy)> {
+        self.hosts
+            .iter()
+            .map(|host| (host.label.clone(), host.client.retry_strategy()))
+            .collect()
+    }
+
+    /// Sets whether the host with the given label
+    /// is considered when automatically selecting the current client.
+    ///
+    /// Hosts which are not selectable stay connected and continue to produce events.
+    ///
+    /// Returns `false` if no host has the given label.
+    pub fn set_selectable(&self, label: &str, selectable: bool) -> bool {
+        match self.hosts.iter().find(|host| host.label == label) {
+            Some(host) => {
+                host.selectable.store(selectable, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Finds the host with the given label,
+    /// or at the given index if `host` is a number and no label matches.
+    fn find_host(&self, host: &str) -> Option<&HostEntry> {
+        self.hosts
+            .iter()
+            .find(|entry| entry.label == host)
+            .or_else(|| host.parse::<usize>().ok().and_then(|i| self.hosts.get(i)))
+    }
+
+    /// Gets whether any of the clients
+    /// currently have a valid connection to their host.
+    ///
+    /// Unlike [`MultiHostClient::wait_for_any_client`], this does not wait.
+    pub fn any_connected(&self) -> bool {
+        self.clients().any(PersistentClient::is_connected)
+    }
+
+    /// Gets whether each host currently has a valid connection, by label.
+    ///
+    /// This is a snapshot and does not wait for any connection attempts.
+    pub fn connection_status(&self) -> Vec<(String, bool)> {
+        self.hosts
+            .iter()
+            .map(|host| (host.label.clone(), host.client.is_connected()))
+            .collect()
+    }
+
+    /// Gets the number of hosts which currently have a valid connection.
+    pub fn connected_count(&self) -> usize {
+        self.clients()
+            .filter(|client| client.is_connected())
+            .count()
+    }
+
     /// Waits until any of the clients
     /// make a valid connection to their host.
-    pub async fn wait_for_any_client(&self) -> Arc<Client> {
+    ///
+    /// Returns an error once every client has given up connecting,
+    /// or [`Error::NoHostConnectedError`] immediately if there are no hosts.
+    pub async fn wait_for_any_client(&self) -> Result<Arc<Client>> {
+        if self.hosts.is_empty() {
+            return Err(Error::NoHostConnectedError);
+        }
+
         let waits = self
-            .clients
-            .iter()
+            .clients()
             .map(|client| Box::pin(client.wait_for_client()));
-        futures::future::select_all(waits).await.0
+        futures::future::select_ok(waits)
+            .await
+            .map(|(client, _)| client)
     }
 
     /// Waits until all of the clients
     /// make a valid connection to their host.
-    pub async fn wait_for_all_clients(&self) -> Vec<Arc<Client>> {
-        let waits = self.clients.iter().map(|client| client.wait_for_client());
+    ///
+    /// Contains an error for each client which gave up connecting.
+    pub async fn wait_for_all_clients(&self) -> Vec<Result<Arc<Client>>> {
+        let waits = self.clients().map(|client| client.wait_for_client());
         futures::future::join_all(waits).await
     }
 
-    /// Attempts to find the current most relevant client.
-    /// This checks for, in order:
+    /// Waits until all of the clients
+    /// make a valid connection to their host, for up to `timeout`.
     ///
-    /// - A currently playing client
-    /// - A paused client (ie has items in the playlist)
-    /// - A connected client
-    async fn get_current_client(
+    /// Returns the client for each host by label,
+    /// or `None` for hosts which did not connect in time.
+    pub async fn wait_for_all_clients_timeout(
         &self,
-    ) -> std::result::Result<Option<&PersistentClient>, CommandError> {
-        self.wait_for_any_client().await;
+        timeout: Duration,
+    ) -> Vec<(String, Option<Arc<Client>>)> {
+        let waits = self.hosts.iter().map(|host| async move {
+            let client = host.client.wait_for_client_timeout(timeout).await;
+            (host.label.clone(), client)
+        });
+
+        futures::future::join_all(waits).await
+    }
+
+    /// Attempts to find the current most relevant host,
+    /// using the configured [`SelectionStrategy`].
+    /// By default, this checks for, in order:
+    ///
+    /// - A currently playing host
+    /// - A paused host (ie has items in the playlist)
+    /// - A connected host
+    ///
+    /// Hosts which have been marked as not selectable are skipped.
+    ///
+    /// The previous selection is reused while it stays connected,
+    /// for up to the selection TTL.
+    async fn get_current_host(&self) -> Result<Option<&HostEntry>> {
+        self.select_host(self.selection_ttl).await
+    }
+
+    /// Selects the current most relevant host,
+    /// reusing the previous selection if it was made within `ttl`.
+    async fn select_host(&self, ttl: Duration) -> Result<Option<&HostEntry>> {
+        let connected_hosts = self.selectable_hosts().await;
+
+        let selected = self
+            .selected
+            .lock()
+            .expect("Failed to get lock on selected host")
+            .clone();
 
-        let connected_clients = self
-            .clients
+        if let Some((label, selected_at)) = selected {
+            let fresh =
+                self.selection_strategy == SelectionStrategy::Sticky || selected_at.elapsed() < ttl;
+
+            if let Some(host) = connected_hosts
+                .iter()
+                .find(|host| fresh && host.label == label)
+            {
+                return Ok(Some(host));
+            }
+        }
+
+        let host = self.choose_host(&connected_hosts).await?;
+
+        *self
+            .selected
+            .lock()
+            .expect("Failed to get lock on selected host") =
+            host.map(|host| (host.label.clone(), Instant::now()));
+
+        Ok(host)
+    }
+
+    /// Gets the connected hosts which can be selected,
+    /// once any client has connected.
+    async fn selectable_hosts(&self) -> Vec<&HostEntry> {
+        // If every client has given up, there is no host to select.
+        let _ = self.wait_for_any_client().await;
+
+        self.hosts
             .iter()
-            .filter(|client| client.is_connected())
-            .collect::<Vec<_>>();
+            .filter(|host| host.is_selectable() && host.client.is_connected())
+            .collect()
+    }
 
-        if connected_clients.is_empty() {
-            Ok(None)
-        } else {
-            let player_states = connected_clients.iter().map(|&client| async move {
-                client.status().await.map(|status| (client, status.state))
-            });
+    /// Chooses the most relevant of the connected hosts
+    /// using the selection strategy, ignoring the previous selection.
+    async fn choose_host<'a>(
+        &self,
+        connected_hosts: &[&'a HostEntry],
+    ) -> Result<Option<&'a HostEntry>> {
+        match self.selection_strategy {
+            SelectionStrategy::PlayingFirst | SelectionStrategy::Sticky => {
+                select_playing_first(connected_hosts).await
+            }
+            SelectionStrategy::FirstConnected => Ok(connected_hosts.first().copied()),
+        }
+    }
 
-            let player_states = futures::future::join_all(player_states)
-                .await
-                .into_iter()
-                .collect::<std::result::Result<Vec<_>, _>>();
+    /// Sets how long the selected host is reused for
+    /// before the current client is selected again.
+    ///
+    /// The selection is always made again if the selected host disconnects.
+    /// Use [`Duration::ZERO`] to select on every command.
+    ///
+    /// This has no effect with [`SelectionStrategy::Sticky`],
+    /// which keeps the selection for as long as the host stays connected.
+    pub fn set_selection_ttl(&mut self, ttl: Duration) {
+        self.selection_ttl = ttl;
+    }
 
-            player_states.map(|player_states| {
-                player_states
-                    .iter()
-                    .find(|(_, state)| state == &PlayState::Playing)
-                    .or_else(|| {
-                        player_states
-                            .iter()
-                            .find(|(_, state)| state == &PlayState::Paused)
-                    })
-                    .or_else(|| {
-                        player_states
-                            .iter()
-                            .find(|(_, state)| state == &PlayState::Stopped)
-                    })
-                    .map(|(client, _)| *client)
-            })
+    /// Clears the selected host,
+    /// so the current client is selected again on the next command.
+    pub fn refresh_current_client(&self) {
+        *self
+            .selected
+            .lock()
+            .expect("Failed to get lock on selected host") = None;
+    }
+
+    /// Waits until the most relevant host (see `get_current_host`)
+    /// has not changed for `window`, and returns its label.
+    ///
+    /// This avoids acting on a host which is only briefly selected
+    /// during a burst of state changes.
+    ///
+    /// Returns `None` if the selection does not settle before `timeout`,
+    /// or settles on no host being available.
+    pub async fn wait_for_stable_active(
+        &self,
+        window: Duration,
+        timeout: Duration,
+    ) -> Option<String> {
+        if self.hosts.is_empty() {
+            return None;
+        }
+
+        let wait = async {
+            let mut receivers = self
+                .clients()
+                .map(PersistentClient::subscribe)
+                .collect::<Vec<_>>();
+
+            let mut active = self.get_current_label().await;
+            let mut stable_since = Instant::now();
+
+            loop {
+                let next_event =
+                    futures::future::select_all(receivers.iter_mut().map(|rx| Box::pin(rx.recv())));
+
+                if timeout_at(stable_since + window, next_event).await.is_err() {
+                    return active;
+                }
+
+                let current = self.get_current_label().await;
+                if current != active {
+                    active = current;
+                    stable_since = Instant::now();
+                }
+            }
+        };
+
+        timeout_at(Instant::now() + timeout, wait)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Chooses the most relevant host without reusing or storing a selection,
+    /// and gets its label, or `None` if no host is available.
+    async fn get_current_label(&self) -> Option<String> {
+        let connected_hosts = self.selectable_hosts().await;
+
+        match self.choose_host(&connected_hosts).await {
+            Ok(host) => host.map(|host| host.label.clone()),
+            Err(_) => None,
         }
     }
 
     /// Runs the provided callback as soon as a connected client is available,
-    /// using the most relevant client (see `get_current_client`).
+    /// using the most relevant client (see `get_current_host`).
     pub async fn with_client<F, Fut, T>(&self, f: F) -> Result<T>
     where
         F: FnOnce(Arc<Client>) -> Fut,
         Fut: Future<Output = T>,
     {
-        let client = self.get_current_client().await;
+        let host = self.require_current_host().await?;
+        host.client.with_client(f).await
+    }
 
-        match client {
-            Ok(Some(client)) => Ok(client.with_client(f).await),
-            Ok(None) => Err(Error::NoHostConnectedError),
-            Err(err) => Err(Error::CommandError(err)),
+    /// Receives on all clients, returning an event from the first one to respond,
+    /// along with the label of its host.
+    ///
+    /// Events missed because a receiver fell behind are skipped,
+    /// as with [`PersistentClient::recv`].
+    ///
+    /// Returns `None` immediately if there are no hosts.
+    pub async fn recv(&mut self) -> Option<HostEvent> {
+        if self.hosts.is_empty() {
+            return None;
         }
-    }
 
-    /// Receives on all clients, returning an event from the first one to respond.
-    pub async fn recv(&mut self) -> std::result::Result<Arc<ConnectionEvent>, RecvError> {
-        let waits = self
-            .clients
-            .iter_mut()
-            .map(|client| Box::pin(client.recv()));
+        let waits = self.hosts.iter_mut().map(|host| {
+            Box::pin(async {
+                let event = host.client.recv().await?;
+                Some(HostEvent {
+                    host: host.label.clone(),
+                    event,
+                })
+            })
+        });
+
         futures::future::select_all(waits).await.0
     }
 
+    /// Creates a single stream of events from all hosts,
+    /// each with the label of its host.
+    ///
+    /// Unlike [`MultiHostClient::recv`], the receivers are kept alive between events
+    /// and polled in turn, so a busy host cannot starve the others.
+    ///
+    /// Hosts added after the stream is created are not included.
+    /// The stream ends once every included host is shut down.
+    pub fn events(&self) -> impl Stream<Item = HostEvent> + Send + 'static {
+        let streams = self.hosts.iter().map(|host| {
+            let label = host.label.clone();
+
+            host.client
+                .event_stream()
+                .map(move |event| HostEvent {
+                    host: label.clone(),
+                    event,
+                })
+                .boxed()
+        });
+
+        stream::select_all(streams)
+    }
+
+    /// Gets the most relevant host (see `get_current_host`),
+    /// or [`Error::NoHostConnectedError`] if there is none.
+    async fn require_current_host(&self) -> Result<&HostEntry> {
+        self.get_current_host()
+            .await?
+            .ok_or(Error::NoHostConnectedError)
+    }
+
+    /// Starts or resumes playback on the most relevant host.
+    /// See [`PersistentClient::play`].
+    pub async fn play(&self) -> Result<()> {
+        let host = self.require_current_host().await?;
+        host.client.play().await.map_err(|err| host.error(err))
+    }
+
+    /// Pauses playback on the most relevant host.
+    /// See [`PersistentClient::pause`].
+    pub async fn pause(&self) -> Result<()> {
+        let host = self.require_current_host().await?;
+        host.client.pause().await.map_err(|err| host.error(err))
+    }
+
+    /// Toggles playback on the most relevant host.
+    /// See [`PersistentClient::toggle_pause`].
+    pub async fn toggle_pause(&self) -> Result<()> {
+        let host = self.require_current_host().await?;
+        host.client
+            .toggle_pause()
+            .await
+            .map_err(|err| host.error(err))
+    }
+
+    /// Stops playback on the most relevant host.
+    /// See [`PersistentClient::stop`].
+    pub async fn stop(&self) -> Result<()> {
+        let host = self.require_current_host().await?;
+        host.client.stop().await.map_err(|err| host.error(err))
+    }
+
+    /// Skips to the next song on the most relevant host.
+    /// See [`PersistentClient::next`].
+    pub async fn next(&self) -> Result<()> {
+        let host = self.require_current_host().await?;
+        host.client.next().await.map_err(|err| host.error(err))
+    }
+
+    /// Goes back to the previous song on the most relevant host.
+    /// See [`PersistentClient::previous`].
+    pub async fn previous(&self) -> Result<()> {
+        let host = self.require_current_host().await?;
+        host.client.previous().await.map_err(|err| host.error(err))
+    }
+
     /// Runs the `status` command on the MPD server.
     pub async fn status(&self) -> Result<Status> {
-        let client = self.get_current_client().await;
-        match client {
-            Ok(Some(client)) => client.status().await.map_err(Error::CommandError),
-            Ok(None) => Err(Error::NoHostConnectedError),
-            Err(err) => Err(Error::CommandError(err)),
+        let host = self.require_current_host().await?;
+        host.client.status().await.map_err(|err| host.error(err))
+    }
+
+    /// Runs the provided command on a specific host,
+    /// referred to by label or index.
+    ///
+    /// Returns [`Error::UnknownHost`] if the host does not exist,
+    /// or [`Error::NoHostConnectedError`] if it is not connected.
+    pub async fn command_on<C: Command>(&self, host: &str, cmd: C) -> Result<C::Response> {
+        match self.find_host(host) {
+            Some(host) if host.client.is_connected() => host
+                .client
+                .command(cmd)
+                .await
+                .map_err(|err| host.error(err)),
+            Some(_) => Err(Error::NoHostConnectedError),
+            None => Err(Error::UnknownHost(host.to_string())),
         }
     }
 
+    /// Runs the `status` command on a specific host,
+    /// referred to by label or index.
+    ///
+    /// Returns [`Error::UnknownHost`] if the host does not exist,
+    /// or [`Error::NoHostConnectedError`] if it is not connected.
+    pub async fn status_on(&self, host: &str) -> Result<Status> {
+        self.command_on(host, commands::Status).await
+    }
+
+    /// Runs the provided command on every host concurrently.
+    ///
+    /// Returns the result for each host by label.
+    /// Hosts which are not connected return [`Error::NoHostConnectedError`].
+    pub async fn command_all<C>(&self, cmd: C) -> Vec<(String, Result<C::Response>)>
+    where
+        C: Command + Clone,
+    {
+        let results = self.hosts.iter().map(|host| {
+            let cmd = cmd.clone();
+
+            async move {
+                let result = if host.client.is_connected() {
+                    host.client
+                        .command(cmd)
+                        .await
+                        .map_err(|err| host.error(err))
+                } else {
+                    Err(Error::NoHostConnectedError)
+                };
+
+                (host.label.clone(), result)
+            }
+        });
+
+        futures::future::join_all(results).await
+    }
+
+    /// Sets the volume on every host concurrently,
+    /// clamped to the range `0..=100`.
+    ///
+    /// Returns the result for each host by label.
+    /// Hosts which are not connected return [`Error::NoHostConnectedError`].
+    pub async fn set_volume_all(&self, volume: u8) -> Vec<(String, Result<()>)> {
+        self.command_all(commands::SetVolume(volume.min(100))).await
+    }
+
+    /// Adjusts the volume on every host concurrently by `delta`,
+    /// clamping each host's new volume to the range `0..=100`.
+    ///
+    /// Hosts without a mixer are left unchanged.
+    ///
+    /// Returns the result for each host by label.
+    /// Hosts which are not connected return [`Error::NoHostConnectedError`].
+    pub async fn adjust_volume_all(&self, delta: i16) -> Vec<(String, Result<()>)> {
+        let results = self.hosts.iter().map(|host| async move {
+            let result = if host.client.is_connected() {
+                host.client
+                    .adjust_volume(delta)
+                    .await
+                    .map_err(|err| host.error(err))
+            } else {
+                Err(Error::NoHostConnectedError)
+            };
+
+            (host.label.clone(), result)
+        });
+
+        futures::future::join_all(results).await
+    }
+
+    /// Runs the `status` command on every host concurrently,
+    /// sharing a single deadline of `budget` between them.
+    ///
+    /// Returns the status for each host by label,
+    /// or `None` for hosts which are disconnected, fail,
+    /// or do not respond before the deadline.
+    pub async fn status_all_timeout(&self, budget: Duration) -> Vec<(String, Option<Status>)> {
+        let deadline = Instant::now() + budget;
+
+        let statuses = self.hosts.iter().map(|host| async move {
+            let status = if host.client.is_connected() {
+                timeout_at(deadline, host.client.status())
+                    .await
+                    .ok()
+                    .and_then(|status| status.ok())
+            } else {
+                None
+            };
+
+            (host.label.clone(), status)
+        });
+
+        futures::future::join_all(statuses).await
+    }
+
+    /// Runs the `status` command on every connected host concurrently.
+    ///
+    /// Returns the status for each host by label.
+    /// Hosts which are disconnected or fail are omitted.
+    pub async fn aggregate_status(&self) -> Vec<(String, Status)> {
+        let statuses = self
+            .hosts
+            .iter()
+            .filter(|host| host.client.is_connected())
+            .map(|host| async move {
+                let status = host.client.status().await.ok()?;
+                Some((host.label.clone(), status))
+            });
+
+        futures::future::join_all(statuses)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Gets the label of the first host which is currently playing,
+    /// or `None` if nothing is playing on any connected host.
+    pub async fn any_playing(&self) -> Option<String> {
+        self.aggregate_status()
+            .await
+            .into_iter()
+            .find(|(_, status)| status.state == PlayState::Playing)
+            .map(|(label, _)| label)
+    }
+
+    /// Gets the current song and status of the most relevant host.
+    /// See [`PersistentClient::now_playing`].
+    pub async fn now_playing(&self) -> Result<NowPlaying> {
+        let host = self.require_current_host().await?;
+        host.client
+            .now_playing()
+            .await
+            .map_err(|err| host.error(err))
+    }
+
     /// Runs the `currentsong` command on the MPD server.
     pub async fn current_song(&self) -> Result<Option<SongInQueue>> {
-        match self.get_current_client().await {
-            Ok(Some(client)) => client.current_song().await.map_err(Error::CommandError),
-            Ok(None) => Err(Error::NoHostConnectedError),
-            Err(err) => Err(Error::CommandError(err)),
+        let host = self.require_current_host().await?;
+        host.client
+            .current_song()
+            .await
+            .map_err(|err| host.error(err))
+    }
+}
+
+/// Commands are sent to the most relevant host (see `get_current_host`),
+/// and events are received from every host.
+impl MpdClient for MultiHostClient {
+    fn command<'a, C>(&'a self, cmd: C) -> BoxFuture<'a, Result<C::Response>>
+    where
+        C: Command + Send + 'a,
+        C::Response: Send,
+    {
+        Box::pin(async move {
+            let host = self.require_current_host().await?;
+            host.client
+                .command(cmd)
+                .await
+                .map_err(|err| host.error(err))
+        })
+    }
+
+    fn status(&self) -> BoxFuture<'_, Result<Status>> {
+        Box::pin(MultiHostClient::status(self))
+    }
+
+    fn current_song(&self) -> BoxFuture<'_, Result<Option<SongInQueue>>> {
+        Box::pin(MultiHostClient::current_song(self))
+    }
+
+    fn is_connected(&self) -> bool {
+        self.any_connected()
+    }
+
+    fn event_stream(&self) -> BoxStream<'static, Arc<ConnectionEvent>> {
+        self.events().map(|event| event.event).boxed()
+    }
+}
+
+/// Checks that no two hosts have the same label,
+/// returning [`Error::DuplicateHost`] for the first repeated label.
+fn check_unique_labels<'a>(labels: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    let mut seen = HashSet::new();
+
+    for label in labels {
+        if !seen.insert(label) {
+            return Err(Error::DuplicateHost(label.to_string()));
         }
     }
+
+    Ok(())
+}
+
+/// Selects a playing host, then a paused host, then any connected host.
+async fn select_playing_first<'a>(
+    connected_hosts: &[&'a HostEntry],
+) -> Result<Option<&'a HostEntry>> {
+    if connected_hosts.is_empty() {
+        return Ok(None);
+    }
+
+    let player_states = connected_hosts.iter().map(|&host| async move {
+        host.client
+            .status()
+            .await
+            .map(|status| (host, status.state))
+            .map_err(|err| host.error(err))
+    });
+
+    let player_states = futures::future::join_all(player_states)
+        .await
+        .into_iter()
+        .collect::<std::result::Result<Vec<_>, _>>();
+
+    player_states.map(|player_states| {
+        player_states
+            .iter()
+            .find(|(_, state)| state == &PlayState::Playing)
+            .or_else(|| {
+                player_states
+                    .iter()
+                    .find(|(_, state)| state == &PlayState::Paused)
+            })
+            .or_else(|| {
+                player_states
+                    .iter()
+                    .find(|(_, state)| state == &PlayState::Stopped)
+            })
+            .map(|(host, _)| *host)
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::{FakeServer, Reply};
+    use std::sync::atomic::AtomicUsize;
 
     #[tokio::test]
+    #[ignore = "requires a running MPD server"]
     async fn test() {
+        let client = MultiHostClient::new(["localhost:6600", "chloe:6600"], Duration::from_secs(5));
+
+        client.init();
+        client.wait_for_all_clients().await;
+
+        let current_host = client
+            .get_current_host()
+            .await
+            .map(|host| host.map(|host| host.label.clone()));
+        println!("{current_host:?}");
+    }
+
+    #[tokio::test]
+    async fn command_all_disconnected() {
+        let client = MultiHostClient::new(["localhost:1", "localhost:2"], Duration::from_secs(5));
+
+        assert!(!client.any_connected());
+        assert_eq!(client.connected_count(), 0);
+        assert_eq!(
+            client.connection_status(),
+            [
+                ("localhost:1".to_string(), false),
+                ("localhost:2".to_string(), false)
+            ]
+        );
+
+        let results = client.command_all(mpd_client::commands::Ping).await;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "localhost:1");
+        assert_eq!(results[1].0, "localhost:2");
+        assert!(results
+            .iter()
+            .all(|(_, result)| matches!(result, Err(Error::NoHostConnectedError))));
+    }
+
+    #[tokio::test]
+    async fn command_on_disconnected() {
+        let client = MultiHostClient::with_named_hosts(
+            &[("living room", "localhost:1")],
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        for host in ["living room", "0"] {
+            assert!(matches!(
+                client.status_on(host).await,
+                Err(Error::NoHostConnectedError)
+            ));
+        }
+
+        for host in ["1", "bedroom"] {
+            assert!(matches!(
+                client.status_on(host).await,
+                Err(Error::UnknownHost(label)) if label == host
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn add_and_remove_host() {
+        let server = FakeServer::bind().await;
+        let addr = server.addr();
+        server.serve();
+
+        let mut client = MultiHostClient::new(Vec::<String>::new(), Duration::from_millis(50));
+        client
+            .add_host(addr.clone(), Duration::from_millis(50))
+            .unwrap();
+        assert!(matches!(
+            client.add_host(addr.clone(), Duration::from_millis(50)),
+            Err(Error::DuplicateHost(label)) if label == addr
+        ));
+
+        let current = tokio::time::timeout(Duration::from_secs(5), client.get_current_host())
+            .await
+            .expect("Timed out waiting for host")
+            .expect("Failed to select host");
+
+        assert_eq!(current.map(|host| host.label.as_str()), Some(addr.as_str()));
+
+        assert!(client.remove_host(&addr));
+        assert!(!client.remove_host(&addr));
+        assert!(!client.any_connected());
+    }
+
+    #[tokio::test]
+    async fn no_hosts_after_removing_last() {
+        let mut client = MultiHostClient::new(["localhost:1"], Duration::from_secs(5));
+        assert!(client.remove_host("localhost:1"));
+
+        let status = tokio::time::timeout(Duration::from_secs(1), client.status())
+            .await
+            .expect("status should not wait");
+        assert!(matches!(status, Err(Error::NoHostConnectedError)));
+
+        assert!(matches!(
+            client.wait_for_any_client().await,
+            Err(Error::NoHostConnectedError)
+        ));
+        assert!(client.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn events_end_when_hosts_removed() {
+        let mut client =
+            MultiHostClient::new(["localhost:1", "localhost:2"], Duration::from_secs(5));
+        let mut events = client.events();
+
+        assert!(client.remove_host("localhost:1"));
+        assert!(client.remove_host("localhost:2"));
+
+        let next = tokio::time::timeout(Duration::from_secs(1), events.next()).await;
+        assert!(matches!(next, Ok(None)));
+    }
+
+    #[tokio::test]
+    async fn sticky_selection() {
+        let mut hosts = vec![];
+        for _ in 0..2 {
+            let server = FakeServer::bind().await;
+            hosts.push(server.addr());
+            server.serve();
+        }
+
+        let client = MultiHostClient::with_selection_strategy(
+            hosts.clone(),
+            Duration::from_millis(50),
+            SelectionStrategy::Sticky,
+        );
+        client.init();
+        client.wait_for_all_clients().await;
+
+        let current_label = || async {
+            let host = client
+                .get_current_host()
+                .await
+                .expect("Failed to select host");
+            host.map(|host| host.label.clone())
+        };
+
+        client.set_selectable(&hosts[0], false);
+        assert_eq!(current_label().await.as_ref(), Some(&hosts[1]));
+
+        // The first host would be preferred, but the selection sticks.
+        client.set_selectable(&hosts[0], true);
+        assert_eq!(current_label().await.as_ref(), Some(&hosts[1]));
+    }
+
+    #[tokio::test]
+    async fn cached_selection() {
+        let mut hosts = vec![];
+        for _ in 0..2 {
+            let server = FakeServer::bind().await;
+            hosts.push(server.addr());
+            server.serve();
+        }
+
+        let client = MultiHostClient::new(hosts.clone(), Duration::from_millis(50));
+        client.init();
+        client.wait_for_all_clients().await;
+
+        let current_label = || async {
+            let host = client
+                .get_current_host()
+                .await
+                .expect("Failed to select host");
+            host.map(|host| host.label.clone())
+        };
+
+        client.set_selectable(&hosts[0], false);
+        assert_eq!(current_label().await.as_ref(), Some(&hosts[1]));
+
+        client.set_selectable(&hosts[0], true);
+        assert_eq!(current_label().await.as_ref(), Some(&hosts[1]));
+
+        client.refresh_current_client();
+        assert_eq!(current_label().await.as_ref(), Some(&hosts[0]));
+    }
+
+    #[tokio::test]
+    async fn stable_active_waits_for_full_window() {
+        // Index of the playing host, which changes on `play`.
+        let playing = Arc::new(AtomicUsize::new(0));
+        let mut hosts = vec![];
+
+        for index in 0..2 {
+            let server = FakeServer::bind().await;
+            hosts.push(server.addr());
+
+            let playing = playing.clone();
+            let player_changed = AtomicBool::new(false);
+            server.serve_with(move |_, line| match line {
+                "play" => {
+                    playing.store(index, Ordering::SeqCst);
+                    player_changed.store(true, Ordering::SeqCst);
+                    Reply::Default
+                }
+                "idle" if player_changed.swap(false, Ordering::SeqCst) => {
+                    Reply::Raw("changed: player\nOK\n".to_string())
+                }
+                "status" => {
+                    let state = if playing.load(Ordering::SeqCst) == index {
+                        "play"
+                    } else {
+                        "stop"
+                    };
+                    Reply::Raw(format!(
+                        "repeat: 0\nrandom: 0\nconsume: 0\nstate: {state}\nOK\n"
+                    ))
+                }
+                _ => Reply::Default,
+            });
+        }
+
+        let client = MultiHostClient::new(hosts.clone(), Duration::from_secs(60));
+        client.init();
+        client.wait_for_all_clients().await;
+
+        let window = Duration::from_millis(300);
+        let start = Instant::now();
+        let (active, ()) = tokio::join!(
+            client.wait_for_stable_active(window, Duration::from_secs(5)),
+            async {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                client
+                    .command_on(&hosts[1], commands::Play::current())
+                    .await
+                    .unwrap();
+            }
+        );
+
+        assert_eq!(active.as_ref(), Some(&hosts[1]));
+        assert!(start.elapsed() >= Duration::from_millis(100) + window);
+
+        // Waiting does not change the selection used for commands.
+        assert!(client.selected.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn stable_active_times_out() {
+        let server = FakeServer::bind().await;
+        let addr = server.addr();
+        server.serve();
+
+        let client = MultiHostClient::new([addr], Duration::from_secs(60));
+        client.init();
+        client.wait_for_all_clients().await;
+
+        let start = Instant::now();
+        let active = client
+            .wait_for_stable_active(Duration::from_secs(5), Duration::from_millis(100))
+            .await;
+
+        assert!(active.is_none());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn named_hosts_labels() {
+        let client = MultiHostClient::with_named_hosts(
+            &[("living room", "localhost:1"), ("bedroom", "localhost:2")],
+            Duration::from_secs(5),
+        )
+        .unwrap();
+
+        let labels = client
+            .retry_configs()
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect::<Vec<_>>();
+
+        assert_eq!(labels, ["living room", "bedroom"]);
+        assert!(client.set_selectable("bedroom", false));
+        assert!(!client.set_selectable("localhost:2", false));
+    }
+
+    #[test]
+    fn duplicate_names_rejected() {
+        let named = MultiHostClient::with_named_hosts(
+            &[("bedroom", "localhost:1"), ("bedroom", "localhost:2")],
+            Duration::from_secs(5),
+        );
+        assert!(matches!(named, Err(Error::DuplicateHost(name)) if name == "bedroom"));
+
+        let built = MultiHostClient::builder()
+            .add_host("bedroom", "localhost:1")
+            .add_host_with(
+                "bedroom",
+                PersistentClientBuilder::new().host("localhost:2".to_string()),
+            )
+            .build();
+        assert!(matches!(built, Err(Error::DuplicateHost(name)) if name == "bedroom"));
+    }
+
+    #[tokio::test]
+    async fn aggregate_status_omits_disconnected() {
+        let server = FakeServer::bind().await;
+        let addr = server.addr();
+        server.serve();
+
         let client = MultiHostClient::new(
-            vec!["localhost:6600".into_string(), "chloe:6600".into_string()],
+            [addr.clone(), "localhost:1".to_string()],
             Duration::from_secs(5),
         );
+        client.init();
+        client.wait_for_any_client().await.unwrap();
+
+        let statuses = client.aggregate_status().await;
+        assert_eq!(statuses.len(), 1);
+        assert_eq!(statuses[0].0, addr);
+        assert_eq!(statuses[0].1.state, PlayState::Stopped);
+
+        assert!(client.any_playing().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn status_all_timeout_skips_hung_host() {
+        let server = FakeServer::bind().await;
+        let addr = server.addr();
+        server.serve();
+
+        // Connects, but never answers `status`.
+        let hung_server = FakeServer::bind().await;
+        let hung_addr = hung_server.addr();
+        hung_server.hang();
 
+        let client =
+            MultiHostClient::new([addr.clone(), hung_addr.clone()], Duration::from_secs(5));
         client.init();
         client.wait_for_all_clients().await;
+        assert_eq!(client.connected_count(), 2);
 
-        let current_client = client.get_current_client().await;
-        println!("{current_client:?}");
+        let start = Instant::now();
+        let statuses = client.status_all_timeout(Duration::from_millis(200)).await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[0].0, addr);
+        assert!(statuses[0].1.is_some());
+        assert_eq!(statuses[1].0, hung_addr);
+        assert!(statuses[1].1.is_none());
+    }
+
+    #[tokio::test]
+    async fn volume_all_clamped_per_host() {
+        let mut hosts = vec![];
+        let mut volumes = vec![];
+
+        for initial in [30, 90] {
+            let server = FakeServer::bind().await;
+            hosts.push(server.addr());
+
+            let volume = Arc::new(AtomicUsize::new(initial));
+            volumes.push(volume.clone());
+            server.serve_with(move |_, line| match line.split_once(' ') {
+                Some(("setvol", value)) => {
+                    volume.store(value.parse().unwrap(), Ordering::SeqCst);
+                    Reply::Default
+                }
+                _ if line == "status" => Reply::Raw(format!(
+                    "volume: {}\nrepeat: 0\nrandom: 0\nconsume: 0\nstate: stop\nOK\n",
+                    volume.load(Ordering::SeqCst)
+                )),
+                _ => Reply::Default,
+            });
+        }
+        hosts.push("localhost:1".to_string());
+
+        let client = MultiHostClient::new(hosts.clone(), Duration::from_secs(5));
+        client.init();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while client.connected_count() < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("servers should connect");
+
+        let volumes = || {
+            volumes
+                .iter()
+                .map(|volume| volume.load(Ordering::SeqCst))
+                .collect::<Vec<_>>()
+        };
+
+        // Each host is adjusted from its own volume.
+        client.adjust_volume_all(20).await;
+        assert_eq!(volumes(), [50, 100]);
+
+        let results = client.adjust_volume_all(-500).await;
+        assert_eq!(volumes(), [0, 0]);
+        assert_eq!(
+            results.iter().map(|(label, _)| label).collect::<Vec<_>>(),
+            hosts.iter().collect::<Vec<_>>()
+        );
+        assert!(results[0].1.is_ok() && results[1].1.is_ok());
+        assert!(matches!(results[2].1, Err(Error::NoHostConnectedError)));
+
+        client.adjust_volume_all(500).await;
+        assert_eq!(volumes(), [100, 100]);
+
+        client.set_volume_all(10).await;
+        assert_eq!(volumes(), [10, 10]);
+
+        let results = client.set_volume_all(150).await;
+        assert_eq!(volumes(), [100, 100]);
+        assert!(results[0].1.is_ok() && results[1].1.is_ok());
+        assert!(matches!(results[2].1, Err(Error::NoHostConnectedError)));
+    }
+
+    #[tokio::test]
+    async fn builder_per_host_options() {
+        let client = MultiHostClient::builder()
+            .retry_interval(Duration::from_secs(10))
+            .add_host("local", "localhost:1")
+            .add_host_with(
+                "remote",
+                PersistentClientBuilder::new()
+                    .host("localhost:2".to_string())
+                    .retry_interval(Duration::from_secs(30))
+                    .password("secret".to_string()),
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            client.retry_configs(),
+            [
+                (
+                    "local".to_string(),
+                    RetryStrategy::Fixed(Duration::from_secs(10))
+                ),
+                (
+                    "remote".to_string(),
+                    RetryStrategy::Fixed(Duration::from_secs(30))
+                ),
+            ]
+        );
     }
 }
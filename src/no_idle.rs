@@ -0,0 +1,242 @@
+//! Stream adapter for holding `idle` back from the server.
+
+use crate::socket::Stream;
+use futures::task::AtomicWaker;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+const IDLE: &[u8] = b"idle\n";
+const NOIDLE: &[u8] = b"noidle\n";
+/// Response to cancelling idle when nothing has changed.
+const NOIDLE_RESPONSE: &[u8] = b"OK\n";
+
+/// Shared switch for holding off `idle` on a [`NoIdleStream`],
+/// such as for the length of a command session.
+#[derive(Debug, Default)]
+pub(crate) struct IdleHold {
+    holds: AtomicUsize,
+    /// Woken once the last hold is released, to send the held `idle`.
+    waker: AtomicWaker,
+}
+
+impl IdleHold {
+    /// Holds off `idle` until the returned guard is dropped.
+    pub(crate) fn hold(self: &Arc<Self>) -> IdleHoldGuard {
+        self.holds.fetch_add(1, Ordering::SeqCst);
+        IdleHoldGuard(self.clone())
+    }
+
+    fn is_held(&self) -> bool {
+        self.holds.load(Ordering::SeqCst) > 0
+    }
+}
+
+/// Releases its hold on `idle` when dropped.
+#[derive(Debug)]
+pub(crate) struct IdleHoldGuard(Arc<IdleHold>);
+
+impl Drop for IdleHoldGuard {
+    fn drop(&mut self) {
+        if self.0.holds.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.waker.wake();
+        }
+    }
+}
+
+/// Stream which holds `idle` back from the server.
+///
+/// `mpd_client` enters idle whenever no commands are running,
+/// and cancels it with `noidle` before sending the next command.
+/// While `idle` is held, both are answered here instead,
+/// as if nothing changed while idling.
+///
+/// Without an [`IdleHold`], `idle` is never sent,
+/// so changes must be found by polling.
+/// Otherwise, a held `idle` is sent once the hold is released,
+/// and the server then reports any changes made in the meantime.
+pub(crate) struct NoIdleStream {
+    inner: Box<dyn Stream>,
+    hold: Option<Arc<IdleHold>>,
+    /// Local responses still to be read, which are returned before the server's.
+    response: Vec<u8>,
+    /// Whether an `idle` has been held back and not yet cancelled or sent.
+    idle_held: bool,
+    /// Number of bytes of the held `idle` already sent to the server.
+    idle_sent: usize,
+}
+
+impl NoIdleStream {
+    /// Creates a stream which never sends `idle`.
+    pub(crate) fn new(inner: Box<dyn Stream>) -> Self {
+        Self {
+            inner,
+            hold: None,
+            response: vec![],
+            idle_held: false,
+            idle_sent: 0,
+        }
+    }
+
+    /// Creates a stream which only holds `idle` back while `hold` is held.
+    pub(crate) fn with_hold(inner: Box<dyn Stream>, hold: Arc<IdleHold>) -> Self {
+        Self {
+            hold: Some(hold),
+            ..Self::new(inner)
+        }
+    }
+
+    fn is_held(&self) -> bool {
+        self.hold.as_ref().is_none_or(|hold| hold.is_held())
+    }
+
+    /// Sends the held `idle` to the server if the hold has been released.
+    fn poll_send_idle(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Once started, the `idle` must be finished even if it is held again.
+        if !self.idle_held || (self.idle_sent == 0 && self.is_held()) {
+            return Poll::Ready(Ok(()));
+        }
+
+        while self.idle_sent < IDLE.len() {
+            let written =
+                ready!(Pin::new(&mut self.inner).poll_write(cx, &IDLE[self.idle_sent..]))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+
+            self.idle_sent += written;
+        }
+
+        ready!(Pin::new(&mut self.inner).poll_flush(cx))?;
+
+        self.idle_held = false;
+        self.idle_sent = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for NoIdleStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.response.is_empty() {
+            if let Some(hold) = &this.hold {
+                hold.waker.register(cx.waker());
+            }
+
+            ready!(this.poll_send_idle(cx))?;
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+
+        let len = this.response.len().min(buf.remaining());
+        buf.put_slice(&this.response[..len]);
+        this.response.drain(..len);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for NoIdleStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_send_idle(cx))?;
+
+        // Each command is written in a single call,
+        // so `idle` and `noidle` always arrive as whole writes.
+        match buf {
+            IDLE if this.is_held() => {
+                this.idle_held = true;
+                Poll::Ready(Ok(buf.len()))
+            }
+            NOIDLE if this.idle_held => {
+                this.idle_held = false;
+                this.response.extend_from_slice(NOIDLE_RESPONSE);
+                Poll::Ready(Ok(buf.len()))
+            }
+            _ => Pin::new(&mut this.inner).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn idle_answered_locally() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let mut stream = NoIdleStream::new(Box::new(client));
+
+        stream.write_all(b"idle\n").await.unwrap();
+        stream.write_all(b"noidle\n").await.unwrap();
+        stream.write_all(b"status\n").await.unwrap();
+
+        let mut response = [0; 3];
+        stream.read_exact(&mut response).await.unwrap();
+        assert_eq!(&response, b"OK\n");
+
+        let mut sent = [0; 7];
+        server.read_exact(&mut sent).await.unwrap();
+        assert_eq!(&sent, b"status\n");
+
+        server.write_all(b"OK\n").await.unwrap();
+        stream.read_exact(&mut response).await.unwrap();
+        assert_eq!(&response, b"OK\n");
+    }
+
+    #[tokio::test]
+    async fn idle_sent_once_released() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let hold = Arc::new(IdleHold::default());
+        let mut stream = NoIdleStream::with_hold(Box::new(client), hold.clone());
+
+        let guard = hold.hold();
+        stream.write_all(b"idle\n").await.unwrap();
+        stream.write_all(b"noidle\n").await.unwrap();
+        stream.write_all(b"status\n").await.unwrap();
+        stream.write_all(b"idle\n").await.unwrap();
+
+        let mut response = [0; 3];
+        stream.read_exact(&mut response).await.unwrap();
+        assert_eq!(&response, b"OK\n");
+
+        // The reader waits for the server while `idle` is held,
+        // and sends it once woken by the release.
+        let read = tokio::spawn(async move {
+            let mut response = [0; 3];
+            stream.read_exact(&mut response).await.map(|_| response)
+        });
+
+        let mut sent = [0; 7];
+        server.read_exact(&mut sent).await.unwrap();
+        assert_eq!(&sent, b"status\n");
+
+        drop(guard);
+        let mut sent = [0; 5];
+        server.read_exact(&mut sent).await.unwrap();
+        assert_eq!(&sent, b"idle\n");
+
+        server.write_all(b"OK\n").await.unwrap();
+        assert_eq!(&read.await.unwrap().unwrap(), b"OK\n");
+    }
+}
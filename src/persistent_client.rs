@@ -1,21 +1,119 @@
-use crate::socket::try_get_connection;
-use mpd_client::client::{CommandError, ConnectionEvent};
-use mpd_client::commands::Command;
-use mpd_client::responses::{SongInQueue, Status};
+use crate::address::Address;
+use crate::art_cache::{Art, ArtCache};
+use crate::builder::PersistentClientBuilder;
+use crate::client::MpdClient;
+use crate::commands::{
+    AddUri, ClearError, Config, CurrentPartition, Decoder, Decoders, OutputSet, Partition,
+    Password, QueueChanges, ReadComments, Volume,
+};
+use crate::connector::{Connector, SocketConnector};
+use crate::error::Error;
+use crate::no_idle::IdleHold;
+use crate::player::{NowPlaying, PlaybackOptions, PlayerEvent, PlayerTracker};
+use crate::retry::RetryStrategy;
+use crate::session::CommandSession;
+use crate::socket::{find_local_host, is_local};
+use crate::stats::{ClientStats, StatsCounters};
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{stream, Stream, StreamExt};
+use mpd_client::client::{
+    CommandError, Connection, ConnectionError, ConnectionEvent, ConnectionEvents, Subsystem,
+};
+use mpd_client::commands::{Command, CommandList, SeekMode, SongId, SongPosition};
+use mpd_client::filter::Filter;
+use mpd_client::protocol::command::CommandList as RawCommandList;
+use mpd_client::protocol::MpdProtocolError;
+use mpd_client::responses::{PlayState, Song, SongInQueue, Status};
+use mpd_client::tag::Tag;
 use mpd_client::{commands, Client};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
 use std::future::Future;
-use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
 use tokio::spawn;
-use tokio::sync::broadcast;
 use tokio::sync::broadcast::error::RecvError;
-use tokio::time::sleep;
-use tracing::{debug, error, info};
+use tokio::sync::{broadcast, watch, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep, MissedTickBehavior};
+use tracing::{debug, error, info, warn};
+
+/// MPD error code returned when a command is not permitted.
+const ACK_ERROR_PERMISSION: u64 = 4;
+/// MPD error code returned when the requested item does not exist.
+const ACK_ERROR_NO_EXIST: u64 = 50;
+
+/// Interval to poll the status on once the server is found not to support `idle`.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sticker name used to store lyrics.
+const LYRICS_STICKER: &str = "lyrics";
+/// File extensions checked for lyrics alongside songs, in order.
+const LYRICS_EXTENSIONS: [&str; 2] = ["lrc", "txt"];
 
 #[derive(Debug, Clone)]
 enum State {
     Disconnected,
     Connected(Arc<Client>),
+    /// The maximum number of connection attempts was reached.
+    /// Contains the error from the last attempt.
+    Failed(String),
+}
+
+/// Connection state shared with the background task.
+#[derive(Debug)]
+struct SharedState {
+    state: RwLock<State>,
+    /// Tracked separately so that checking the connection never waits on the lock.
+    connected: AtomicBool,
+}
+
+impl SharedState {
+    fn new(state: State) -> Self {
+        Self {
+            connected: AtomicBool::new(matches!(state, State::Connected(_))),
+            state: RwLock::new(state),
+        }
+    }
+
+    fn set(&self, state: State) {
+        let mut current = self.state.write().expect("Failed to get lock on state");
+
+        self.connected
+            .store(matches!(state, State::Connected(_)), Ordering::Release);
+        *current = state;
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, State> {
+        self.state.read().expect("Failed to get lock on state")
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Acquire)
+    }
+}
+
+/// State of the connection to the server,
+/// published each time it changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Connected to the server.
+    Connected,
+    /// Not connected, either because the connection was lost
+    /// or the client has been shut down.
+    Disconnected,
+    /// Attempting to connect to the server.
+    /// This is kept while attempts continue to fail.
+    Reconnecting,
+    /// The maximum number of connection attempts was reached,
+    /// and no further attempts will be made.
+    Failed,
+    /// The connection was closed after the idle timeout,
+    /// and is re-established by the next command.
+    Idle,
 }
 
 type Channel<T> = (broadcast::Sender<T>, broadcast::Receiver<T>);
@@ -24,125 +122,786 @@ type Channel<T> = (broadcast::Sender<T>, broadcast::Receiver<T>);
 /// if the connection cannot be established or is lost.
 ///
 /// Commands sent to a disconnected client are queued.
+///
+/// Cloning the client is cheap, and all clones share the same connection.
+/// The background task is stopped once every clone has been dropped.
 #[derive(Debug)]
 pub struct PersistentClient {
-    host: String,
-    retry_interval: Duration,
-    state: Arc<RwLock<State>>,
+    /// Addresses of the server, in order of preference.
+    /// Always contains at least one entry.
+    hosts: Vec<String>,
+    /// Index of the address of the current or last connection.
+    active_host: Arc<AtomicUsize>,
+    /// Whether every host is a local connection.
+    /// Determined on first use, as resolving the hosts may block.
+    local: Arc<OnceLock<bool>>,
+    /// Shared with the background task, which reads it before each retry.
+    retry_strategy: Arc<RwLock<RetryStrategy>>,
+    connector: Arc<dyn Connector>,
+    password: Option<Password>,
+    connect_timeout: Duration,
+    /// Interval to poll the status on instead of relying on `idle`.
+    poll_interval: Option<Duration>,
+    /// Whether `idle` is avoided, either by request or because the server rejected it.
+    /// Shared with the default connector.
+    no_idle: Arc<AtomicBool>,
+    /// Holds off `idle` during command sessions.
+    /// Shared with the default connector.
+    idle_hold: Arc<IdleHold>,
+    /// Interval to ping the server on while connected, if enabled.
+    heartbeat_interval: Option<Duration>,
+    /// Time without activity after which the connection is closed, if enabled.
+    idle_timeout: Option<Duration>,
+    activity: Arc<Activity>,
+    /// Whether playback controls error when stopped.
+    strict_controls: bool,
+    /// Consecutive failed attempts after which the client gives up.
+    max_attempts: Option<u32>,
+    state: Arc<SharedState>,
+    stats: Arc<StatsCounters>,
+    /// Error from the most recent connection attempt, if it failed.
+    last_error: Arc<Mutex<Option<String>>>,
     channel: Channel<Arc<ConnectionEvent>>,
     connection_channel: Channel<Arc<Client>>,
+    player_channel: broadcast::Sender<PlayerEvent>,
+    state_channel: broadcast::Sender<ConnectionState>,
+    queue_empty_channel: broadcast::Sender<()>,
+    volume_channel: Arc<watch::Sender<Option<u8>>>,
+    /// Message channels which are re-subscribed to after reconnecting.
+    channels: Arc<Mutex<HashSet<String>>>,
+    /// Partition which is re-selected after reconnecting, if set.
+    partition: Arc<Mutex<Option<String>>>,
+    /// Existing connection to use for the first iteration of the loop.
+    adopted_connection: Arc<Mutex<Option<Connection>>>,
+    /// Notified to drop the current connection and reconnect.
+    drop_connection: Arc<Notify>,
+    /// Notified to skip the delay before the next connection attempt.
+    retry_now: Arc<Notify>,
+    /// Callbacks run on each connect and disconnect.
+    hooks: Arc<Hooks>,
+    /// Album art by song URI, if caching is enabled.
+    art_cache: Option<Arc<Mutex<ArtCache>>>,
+    /// Set to `true` to stop the background task.
+    shutdown: Arc<watch::Sender<bool>>,
 }
 
 impl PersistentClient {
+    /// Creates a new client for a single host.
+    ///
+    /// See [`PersistentClient::builder`] for further options.
     pub fn new(host: String, retry_interval: Duration) -> Self {
-        let channel = broadcast::channel(1024);
+        Self::builder()
+            .host(host)
+            .retry_interval(retry_interval)
+            .build()
+    }
+
+    /// Creates a new client for a single server
+    /// which is reachable on several addresses.
+    ///
+    /// Each connection attempt tries the addresses in order,
+    /// and uses the first which connects.
+    ///
+    /// # Panics
+    ///
+    /// If `addrs` is empty.
+    pub fn new_multi_addr(addrs: Vec<String>, retry_interval: Duration) -> Self {
+        Self::from_builder(PersistentClientBuilder {
+            hosts: addrs,
+            retry_strategy: RetryStrategy::Fixed(retry_interval),
+            ..PersistentClientBuilder::default()
+        })
+    }
+
+    /// Creates a new client for a password-protected server.
+    ///
+    /// The password is sent after each connection is established.
+    /// If it is rejected, the connection attempt is treated as failed and retried.
+    pub fn with_password(host: String, retry_interval: Duration, password: String) -> Self {
+        PersistentClientBuilder::new()
+            .host(host)
+            .retry_interval(retry_interval)
+            .password(password)
+            .build()
+    }
+
+    /// Creates a new client for a server running on this machine.
+    ///
+    /// This uses the socket at `$XDG_RUNTIME_DIR/mpd/socket` or `/run/mpd/socket`
+    /// if either exists, and the localhost TCP address otherwise.
+    /// The socket is only looked for once, when the client is created.
+    pub fn local() -> Self {
+        PersistentClientBuilder::new()
+            .host(find_local_host())
+            .build()
+    }
+
+    /// Creates a new client using the conventional MPD environment variables.
+    /// See [`PersistentClientBuilder::from_env`].
+    pub fn from_env() -> Self {
+        PersistentClientBuilder::from_env().build()
+    }
+
+    /// Creates a new client from a connection string
+    /// of the form `[tls://][password@]host[:port]`.
+    ///
+    /// The host can instead be a unix socket path starting with `/`,
+    /// or an abstract socket name starting with `@`.
+    /// The port defaults to `6600`, and the password is sent after each connection.
+    ///
+    /// Returns [`Error::InvalidAddress`] if the string cannot be parsed.
+    pub fn from_url(url: &str) -> crate::Result<Self> {
+        let address = Address::parse(url)?;
+        let mut builder = PersistentClientBuilder::new().host(address.host);
+
+        if let Some(password) = address.password {
+            builder = builder.password(password);
+        }
+
+        Ok(builder.build())
+    }
+
+    /// Creates a builder for configuring a new client.
+    pub fn builder() -> PersistentClientBuilder {
+        PersistentClientBuilder::new()
+    }
+
+    pub(crate) fn from_builder(builder: PersistentClientBuilder) -> Self {
+        assert!(
+            !builder.hosts.is_empty(),
+            "At least one address is required"
+        );
+
+        let channel = broadcast::channel(builder.event_buffer);
         let connection_channel = broadcast::channel(8);
+        let (player_channel, _) = broadcast::channel(64);
+        let (queue_empty_channel, _) = broadcast::channel(8);
+        let (state_channel, _) = broadcast::channel(16);
+
+        let no_idle = Arc::new(AtomicBool::new(builder.poll_interval.is_some()));
+        let idle_hold = Arc::new(IdleHold::default());
 
         Self {
-            host,
-            retry_interval,
-            state: Arc::new(RwLock::new(State::Disconnected)),
+            hosts: builder.hosts,
+            active_host: Arc::default(),
+            local: Arc::default(),
+            retry_strategy: Arc::new(RwLock::new(builder.retry_strategy)),
+            password: builder.password,
+            connect_timeout: builder.connect_timeout,
+            connector: builder.connector.unwrap_or_else(|| {
+                Arc::new(SocketConnector {
+                    options: builder.connection_options,
+                    no_idle: no_idle.clone(),
+                    idle_hold: idle_hold.clone(),
+                })
+            }),
+            poll_interval: builder.poll_interval,
+            no_idle,
+            idle_hold,
+            heartbeat_interval: builder.heartbeat_interval,
+            idle_timeout: builder.idle_timeout,
+            activity: Arc::default(),
+            strict_controls: builder.strict_controls,
+            max_attempts: builder.max_attempts,
+            state: Arc::new(SharedState::new(State::Disconnected)),
+            stats: Arc::new(StatsCounters::new()),
+            last_error: Arc::default(),
             channel,
             connection_channel,
+            player_channel,
+            state_channel,
+            queue_empty_channel,
+            volume_channel: Arc::new(watch::channel(None).0),
+            channels: Arc::default(),
+            partition: Arc::new(Mutex::new(builder.partition)),
+            adopted_connection: Arc::default(),
+            drop_connection: Arc::default(),
+            retry_now: Arc::default(),
+            hooks: Arc::default(),
+            art_cache: builder
+                .art_cache_capacity
+                .map(|capacity| Arc::new(Mutex::new(ArtCache::new(capacity)))),
+            shutdown: Arc::new(watch::channel(false).0),
         }
     }
 
+    /// Creates a new client from an existing connection to `host`.
+    ///
+    /// The client is immediately connected,
+    /// and once [`PersistentClient::init`] is called it takes over the event stream.
+    /// If the connection is lost, the client reconnects to `host` as normal.
+    pub fn adopt(client: Client, events: ConnectionEvents, host: String) -> Self {
+        let persistent_client = PersistentClientBuilder::new().host(host).build();
+
+        persistent_client
+            .state
+            .set(State::Connected(Arc::new(client.clone())));
+        *persistent_client
+            .adopted_connection
+            .lock()
+            .expect("Failed to get lock on connection") = Some((client, events));
+
+        persistent_client
+    }
+
     /// Attempts to connect to the MPD host
     /// and begins listening to server events.
-    pub fn init(&self) {
-        let host = self.host.clone();
-        let retry_interval = self.retry_interval;
+    ///
+    /// Returns a handle to the background task,
+    /// which can be awaited or aborted to supervise the client.
+    /// The task runs until [`PersistentClient::shutdown`] is called,
+    /// or the client is dropped.
+    /// The handle can be ignored if this is not needed.
+    pub fn init(&self) -> JoinHandle<()> {
+        let hosts = self.hosts.clone();
+        let active_host = self.active_host.clone();
+        let retry_strategy = self.retry_strategy.clone();
+        let connector = self.connector.clone();
+        let password = self.password.clone();
+        let connect_timeout = self.connect_timeout;
+        let poll_interval = self.poll_interval;
+        let no_idle = self.no_idle.clone();
+        let heartbeat_interval = self.heartbeat_interval;
+        let idle_timeout = self.idle_timeout;
+        let activity = self.activity.clone();
         let state = self.state.clone();
         let tx = self.channel.0.clone();
         let conn_tx = self.connection_channel.0.clone();
+        let player_tx = self.player_channel.clone();
+        let queue_empty_tx = self.queue_empty_channel.clone();
+        let volume_tx = self.volume_channel.clone();
+        let channels = self.channels.clone();
+        let partition = self.partition.clone();
+        let mut adopted_connection = self
+            .adopted_connection
+            .lock()
+            .expect("Failed to get lock on connection")
+            .take();
+
+        let drop_connection = self.drop_connection.clone();
+        let retry_now = self.retry_now.clone();
+        let hooks = self.hooks.clone();
+        let stats = self.stats.clone();
+        let last_error = self.last_error.clone();
+        let max_attempts = self.max_attempts;
+        let shutdown = self.shutdown.subscribe();
+        let shutdown_state = self.state.clone();
+        let shutdown_volume_tx = self.volume_channel.clone();
+        let shutdown_state_tx = self.state_channel.clone();
+        let mut state_tx = StatePublisher::new(self.state_channel.clone());
+
+        let run = async move {
+            let mut failures = FailureLog::default();
 
-        spawn(async move {
             loop {
-                debug!("Attempting to connect to {host}");
-                let connection = try_get_connection(&host).await;
+                state_tx.publish(ConnectionState::Reconnecting);
+
+                let connection = match adopted_connection.take() {
+                    Some(connection) => Ok((0, connection)),
+                    None => {
+                        connect_any(&hosts, &*connector, password.as_ref(), connect_timeout).await
+                    }
+                };
 
-                match connection {
-                    Ok(connection) => {
+                let idled = match connection {
+                    Ok((index, connection)) => {
+                        let host = hosts[index].as_str();
+                        active_host.store(index, Ordering::Relaxed);
                         info!("Connected to '{host}'");
+                        failures.reset();
+                        stats.record_connected();
+                        *last_error.lock().expect("Failed to get lock on last error") = None;
 
                         let client = Arc::new(connection.0);
+                        select_partition(&client, &partition).await;
+                        resubscribe(&client, &channels).await;
+
+                        // Registered before the state is set,
+                        // so that a `reconnect` as soon as the client is connected is not missed.
+                        let dropped = drop_connection.notified();
+                        tokio::pin!(dropped);
+                        dropped.as_mut().enable();
 
                         {
-                            *state.write().expect("Failed to get lock on state") =
-                                State::Connected(client.clone());
-                            conn_tx.send(client).expect("Failed to send event");
+                            state.set(State::Connected(client.clone()));
+                            conn_tx.send(client.clone()).expect("Failed to send event");
                         }
+                        state_tx.publish(ConnectionState::Connected);
+                        hooks.connected(&client);
+                        // Runs the disconnect callbacks once the connection is lost,
+                        // or the loop is dropped on shutdown.
+                        let _disconnect = OnDisconnect(&hooks);
 
                         let mut events = connection.1;
+                        let idle_events = tx.subscribe();
+
+                        let mut derived = Derived {
+                            client: &client,
+                            player: PlayerTracker::default(),
+                            player_tx: &player_tx,
+                            queue_length: None,
+                            queue_empty_tx: &queue_empty_tx,
+                            volume_tx: &volume_tx,
+                        };
 
-                        while let Some(event) = events.next().await {
-                            if let ConnectionEvent::ConnectionClosed(err) = event {
-                                error!("Lost connection to '{host}': {err:?}");
-                                *state.write().expect("Failed to get lock on state") =
-                                    State::Disconnected;
+                        let watch = async {
+                            // This is included in the heartbeat,
+                            // as the server may already be unresponsive.
+                            derived.init().await;
 
-                                break;
+                            let poll_interval = poll_interval.or_else(|| {
+                                no_idle
+                                    .load(Ordering::Relaxed)
+                                    .then_some(DEFAULT_POLL_INTERVAL)
+                            });
+
+                            match poll_interval {
+                                Some(poll_interval) => {
+                                    poll_status(
+                                        host,
+                                        &client,
+                                        &mut events,
+                                        poll_interval,
+                                        &tx,
+                                        &mut derived,
+                                    )
+                                    .await;
+                                }
+                                None => {
+                                    watch_events(host, &mut events, &no_idle, &tx, &mut derived)
+                                        .await;
+                                }
                             }
+                        };
 
-                            debug!("Sending event: {event:?}");
+                        let idled = tokio::select! {
+                            () = watch => false,
+                            () = dropped => {
+                                info!("Dropping connection to '{host}'");
+                                false
+                            }
+                            () = heartbeat(&client, heartbeat_interval) => {
+                                error!("Lost connection to '{host}': heartbeat failed");
+                                false
+                            }
+                            () = wait_for_idle(&activity, idle_events, idle_timeout) => {
+                                info!("Closing idle connection to '{host}'");
+                                true
+                            }
+                        };
 
-                            // Wrap in `Arc` because `ConnectionEvent` isn't `Clone`.
-                            tx.send(Arc::new(event)).expect("Failed to send event");
+                        state.set(State::Disconnected);
+                        volume_tx.send_replace(None);
+                        if !idled {
+                            state_tx.publish(ConnectionState::Disconnected);
                         }
+
+                        idled
                     }
                     Err(err) => {
-                        error!("Failed to connect to '{host}': {err:?}");
-                        *state.write().expect("Failed to get lock on state") = State::Disconnected;
+                        failures.record(&hosts, &err);
+                        stats.record_failure();
+                        *last_error.lock().expect("Failed to get lock on last error") =
+                            Some(err.to_string());
+
+                        if max_attempts.is_some_and(|max| failures.attempts >= max) {
+                            error!("Giving up connecting after {} attempts", failures.attempts);
+
+                            state.set(State::Failed(err.to_string()));
+                            state_tx.publish(ConnectionState::Failed);
+                            return;
+                        }
+
+                        state.set(State::Disconnected);
+                        false
                     }
+                };
+
+                if idled {
+                    // Registered before the client is marked idle,
+                    // so that a command as soon as it is marked is not missed.
+                    let woken = activity.wake.notified();
+                    tokio::pin!(woken);
+                    woken.as_mut().enable();
+
+                    activity.idle.store(true, Ordering::SeqCst);
+                    state_tx.publish(ConnectionState::Idle);
+
+                    woken.await;
+                    activity.idle.store(false, Ordering::SeqCst);
+                    continue;
                 }
 
-                sleep(retry_interval).await;
+                let delay = retry_strategy
+                    .read()
+                    .expect("Failed to get lock on retry strategy")
+                    .delay(failures.attempts);
+
+                tokio::select! {
+                    () = sleep(delay) => {}
+                    () = retry_now.notified() => debug!("Retrying connection immediately"),
+                }
             }
-        });
+        };
+
+        spawn(async move {
+            tokio::select! {
+                biased;
+                () = wait_for_shutdown(shutdown) => {
+                    // Dropping the loop drops the connection's event stream,
+                    // so only the state needs resetting.
+                    shutdown_state.set(State::Disconnected);
+                    shutdown_volume_tx.send_replace(None);
+                    let _ = shutdown_state_tx.send(ConnectionState::Disconnected);
+
+                    debug!("Client shut down");
+                }
+                () = run => {}
+            }
+        })
+    }
+
+    /// Gets statistics about the connection to the server,
+    /// such as the number of reconnects.
+    ///
+    /// This is cheap to call and does not wait on the connection.
+    pub fn stats(&self) -> ClientStats {
+        self.stats.snapshot()
+    }
+
+    /// Gets the error from the most recent connection attempt,
+    /// or `None` if it succeeded or no attempt has been made yet.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error
+            .lock()
+            .expect("Failed to get lock on last error")
+            .clone()
+    }
+
+    /// Reconnects to the server as soon as possible.
+    ///
+    /// If connected, the current connection is dropped and re-established.
+    /// If disconnected, the delay before the next connection attempt is skipped.
+    pub fn reconnect(&self) {
+        if self.is_connected() {
+            self.drop_connection.notify_waiters();
+        }
+
+        // This stores a permit if the loop is not waiting yet,
+        // so the next delay is skipped either way.
+        self.retry_now.notify_one();
     }
 
-    /// Gets the client host address or path
+    /// Stops the background task started by [`PersistentClient::init`],
+    /// closing the current connection.
+    /// No further connection attempts are made after this.
+    ///
+    /// This also happens automatically when the client is dropped.
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+        self.state.set(State::Disconnected);
+    }
+
+    /// Gets the address or path of the current connection,
+    /// or of the last connection if currently disconnected.
+    ///
+    /// For clients with multiple addresses,
+    /// this is the first address until a connection is made.
     pub fn host(&self) -> &str {
-        &self.host
+        &self.hosts[self.active_host.load(Ordering::Relaxed)]
+    }
+
+    /// Gets every address of the server, in order of preference.
+    pub fn hosts(&self) -> &[String] {
+        &self.hosts
+    }
+
+    /// Gets the strategy for the delay between connection attempts.
+    pub fn retry_strategy(&self) -> RetryStrategy {
+        *self
+            .retry_strategy
+            .read()
+            .expect("Failed to get lock on retry strategy")
+    }
+
+    /// Sets the strategy for the delay between connection attempts.
+    ///
+    /// This takes effect from the next retry,
+    /// and does not interrupt a delay which has already started.
+    pub fn set_retry_strategy(&self, retry_strategy: RetryStrategy) {
+        *self
+            .retry_strategy
+            .write()
+            .expect("Failed to get lock on retry strategy") = retry_strategy;
+    }
+
+    /// Gets whether the host is a local connection,
+    /// either a unix socket or a TCP address resolving to loopback.
+    ///
+    /// Some features, such as [`PersistentClient::music_directory`],
+    /// are only available over local connections.
+    ///
+    /// For clients with multiple addresses, this is only true if all of them are local.
+    ///
+    /// This is determined on the first call, and cached after that.
+    /// TCP hosts are resolved using the system resolver, so the first call may block.
+    pub fn is_local(&self) -> bool {
+        *self
+            .local
+            .get_or_init(|| self.hosts.iter().all(|host| is_local(host)))
     }
 
     /// Gets whether there is a valid connection to the server
     pub fn is_connected(&self) -> bool {
-        matches!(
-            *self.state.read().expect("Failed to get lock on state"),
-            State::Connected(_)
-        )
+        self.state.is_connected()
+    }
+
+    /// Gets whether the client has given up connecting
+    /// after reaching its maximum number of attempts.
+    ///
+    /// See [`PersistentClientBuilder::max_attempts`].
+    pub fn has_failed(&self) -> bool {
+        matches!(&*self.state.read(), State::Failed(_))
     }
 
     /// Waits for a valid connection to the server to be established.
     /// If already connected, resolves immediately.
-    pub async fn wait_for_client(&self) -> Arc<Client> {
-        {
-            let state = self.state.read().expect("Failed to get lock on state");
+    ///
+    /// Returns [`Error::ConnectionFailed`] once the client has given up connecting.
+    ///
+    /// The wait can be cancelled by dropping the future.
+    pub async fn wait_for_client(&self) -> crate::Result<Arc<Client>> {
+        self.wait_for_new_client(None).await
+    }
+
+    /// Waits for a connection other than `previous` to be established.
+    ///
+    /// The state may still hold the previous client
+    /// for a short time after its connection is lost.
+    async fn wait_for_new_client(
+        &self,
+        previous: Option<&Arc<Client>>,
+    ) -> crate::Result<Arc<Client>> {
+        // Subscribe before checking the state,
+        // so that a connection made in between is not missed.
+        let mut rx = self.connection_channel.0.subscribe();
+        let mut state_rx = self.state_channel.subscribe();
+
+        loop {
+            // Touched on each change too, in case the client became idle while waiting.
+            self.activity.touch();
 
-            if let State::Connected(client) = &*state {
-                return client.clone();
+            match &*self.state.read() {
+                State::Connected(client)
+                    if !previous.is_some_and(|previous| Arc::ptr_eq(client, previous)) =>
+                {
+                    return Ok(client.clone());
+                }
+                State::Failed(err) => return Err(Error::ConnectionFailed(err.clone())),
+                _ => {}
+            }
+
+            tokio::select! {
+                client = rx.recv() => match client {
+                    Ok(client) => return Ok(client),
+                    // Missed connections are always followed by a newer one.
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => unreachable!("Sender is owned by the client"),
+                },
+                // The state is checked again on any change, in case the client failed.
+                _ = state_rx.recv() => {}
             }
         }
+    }
 
-        let mut rx = self.connection_channel.0.subscribe();
-        rx.recv().await.unwrap()
+    /// Waits for a connection, treating a client which has given up
+    /// as a closed connection.
+    async fn connection(&self) -> Result<Arc<Client>, CommandError> {
+        self.wait_for_client()
+            .await
+            .map_err(|_| CommandError::ConnectionClosed)
+    }
+
+    /// Waits for a valid connection to the server to be established,
+    /// for up to `timeout`.
+    /// If already connected, resolves immediately.
+    ///
+    /// Returns `None` if no connection is established in time,
+    /// or the client gives up connecting.
+    pub async fn wait_for_client_timeout(&self, timeout: Duration) -> Option<Arc<Client>> {
+        tokio::time::timeout(timeout, self.wait_for_client())
+            .await
+            .ok()
+            .and_then(Result::ok)
+    }
+
+    /// Waits for a valid connection to the server to be established,
+    /// or for the client to be shut down.
+    ///
+    /// Returns `None` if the client is shut down first,
+    /// or gives up connecting.
+    pub async fn wait_for_client_or_shutdown(&self) -> Option<Arc<Client>> {
+        tokio::select! {
+            biased;
+            () = wait_for_shutdown(self.shutdown.subscribe()) => None,
+            client = self.wait_for_client() => client.ok(),
+        }
+    }
+
+    /// Registers a callback to run each time a connection is established,
+    /// including the first.
+    ///
+    /// This can be used to re-apply server settings after reconnecting.
+    /// The callback runs on the background task, before events are read,
+    /// so any commands should be sent from a spawned task.
+    /// All registered callbacks run, in the order they were registered.
+    pub fn on_connect<F>(&self, callback: F)
+    where
+        F: Fn(Arc<Client>) + Send + Sync + 'static,
+    {
+        self.hooks
+            .connect
+            .lock()
+            .expect("Failed to get lock on hooks")
+            .push(Arc::new(callback));
+    }
+
+    /// Registers a callback to run each time the connection is lost,
+    /// or the client is shut down while connected.
+    ///
+    /// All registered callbacks run, in the order they were registered.
+    pub fn on_disconnect<F>(&self, callback: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.hooks
+            .disconnect
+            .lock()
+            .expect("Failed to get lock on hooks")
+            .push(Arc::new(callback));
+    }
+
+    /// Waits for the current connection to the server to be lost.
+    /// If not connected, resolves immediately.
+    ///
+    /// The wait can be cancelled by dropping the future.
+    pub async fn wait_for_disconnect(&self) {
+        // Subscribe before checking the state,
+        // so that a disconnect in between is not missed.
+        let mut rx = self.state_channel.subscribe();
+
+        while self.is_connected() {
+            match rx.recv().await {
+                // The state is updated before each change is sent,
+                // so it is checked again on any change.
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => unreachable!("Sender is owned by the client"),
+            }
+        }
     }
 
     /// Runs the provided callback as soon as the connected client is available.
-    pub async fn with_client<F, Fut, T>(&self, f: F) -> T
+    ///
+    /// Returns [`Error::ConnectionFailed`] if the client gives up connecting.
+    pub async fn with_client<F, Fut, T>(&self, f: F) -> crate::Result<T>
     where
         F: FnOnce(Arc<Client>) -> Fut,
         Fut: Future<Output = T>,
     {
-        let client = self.wait_for_client().await;
+        let client = self.wait_for_client().await?;
+        Ok(f(client).await)
+    }
+
+    /// Runs the provided fallible callback as soon as the connected client is available.
+    ///
+    /// A client which has given up connecting is treated as a closed connection.
+    async fn with_connection<F, Fut, T>(&self, f: F) -> Result<T, CommandError>
+    where
+        F: FnOnce(Arc<Client>) -> Fut,
+        Fut: Future<Output = Result<T, CommandError>>,
+    {
+        let client = self.connection().await?;
         f(client).await
     }
 
+    /// Runs the provided callback as soon as the connected client is available,
+    /// re-running it from the start if the connection is lost partway through.
+    ///
+    /// After a lost connection, the callback is retried up to `max_retries` times,
+    /// each time waiting for the client to reconnect first.
+    /// Other errors are returned immediately.
+    ///
+    /// The callback should be idempotent, as it may have partially run before failing.
+    pub async fn with_retry<F, Fut, T>(&self, max_retries: usize, f: F) -> Result<T, CommandError>
+    where
+        F: Fn(Arc<Client>) -> Fut,
+        Fut: Future<Output = Result<T, CommandError>>,
+    {
+        let mut client = self.connection().await?;
+        let mut retries = 0;
+
+        loop {
+            match f(client.clone()).await {
+                Err(err) if is_connection_lost(&err) && retries < max_retries => {
+                    debug!("Connection lost during command, retrying: {err:?}");
+
+                    retries += 1;
+                    client = self
+                        .wait_for_new_client(Some(&client))
+                        .await
+                        .map_err(|_| CommandError::ConnectionClosed)?;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Runs the provided callback with a session
+    /// for sending a sequence of commands on the same connection.
+    ///
+    /// Idle is left once before the first command,
+    /// and only re-entered once the callback has finished,
+    /// however long it waits between commands.
+    /// Events which happen in the meantime are received after the session.
+    ///
+    /// Idle is only held off with the default connector,
+    /// and not for connections from [`PersistentClient::adopt`].
+    ///
+    /// Returns [`Error::ConnectionFailed`] if the client gives up connecting.
+    pub async fn with_command_session<F, Fut, T>(&self, f: F) -> crate::Result<T>
+    where
+        F: FnOnce(CommandSession) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let _hold = self.idle_hold.hold();
+
+        self.with_client(|client| f(CommandSession::new(client)))
+            .await
+    }
+
     /// Receives an event from the MPD server.
-    pub async fn recv(&mut self) -> Result<Arc<ConnectionEvent>, RecvError> {
+    ///
+    /// If events were missed because the receiver fell behind,
+    /// they are skipped with a warning and the next available event is returned.
+    ///
+    /// Returns `None` once the client is shut down.
+    pub async fn recv(&mut self) -> Option<Arc<ConnectionEvent>> {
+        let shutdown = self.shutdown.subscribe();
         let rx = &mut self.channel.1;
-        rx.recv().await
+
+        loop {
+            tokio::select! {
+                biased;
+                () = wait_for_shutdown(shutdown.clone()) => return None,
+                event = rx.recv() => match event {
+                    Ok(event) => return Some(event),
+                    Err(RecvError::Lagged(count)) => {
+                        warn!("Receiver fell behind, skipped {count} events");
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+            }
+        }
     }
 
     /// Creates a new receiver to be able to receive events
@@ -153,43 +912,1485 @@ impl PersistentClient {
         self.channel.0.subscribe()
     }
 
+    /// Creates a stream of events from the MPD server.
+    ///
+    /// Events missed because the stream fell behind are skipped with a warning,
+    /// rather than ending the stream.
+    /// The stream ends once the client is shut down.
+    pub fn event_stream(&self) -> impl Stream<Item = Arc<ConnectionEvent>> + Send + 'static {
+        let rx = self.subscribe();
+        let shutdown = self.shutdown.subscribe();
+
+        stream::unfold((rx, shutdown), |(mut rx, shutdown)| async move {
+            loop {
+                tokio::select! {
+                    biased;
+                    () = wait_for_shutdown(shutdown.clone()) => return None,
+                    event = rx.recv() => match event {
+                        Ok(event) => return Some((event, (rx, shutdown))),
+                        Err(RecvError::Lagged(count)) => {
+                            warn!("Event stream fell behind, skipped {count} events");
+                        }
+                        Err(RecvError::Closed) => return None,
+                    },
+                }
+            }
+        })
+    }
+
+    /// Creates a stream of the current song,
+    /// which yields each time the playing song changes.
+    ///
+    /// The current song is yielded first.
+    /// Songs are compared by id, so changes to the same song are not yielded.
+    /// The stream ends once the client is shut down.
+    pub fn song_changes(&self) -> impl Stream<Item = Option<SongInQueue>> + Send + 'static {
+        let client = self.clone();
+        let events = self.event_stream().boxed();
+
+        stream::unfold(
+            (client, events, None),
+            |(client, mut events, last_id)| async move {
+                // The current song is always fetched on the first poll.
+                let mut fetch = last_id.is_none();
+
+                loop {
+                    if fetch {
+                        let connection = client.wait_for_client_or_shutdown().await?;
+
+                        if let Ok(song) = connection.command(commands::CurrentSong).await {
+                            let id = song.as_ref().map(|song| song.id);
+                            if last_id != Some(id) {
+                                return Some((song, (client, events, Some(id))));
+                            }
+                        }
+                    }
+
+                    let event = events.next().await?;
+                    fetch = matches!(*event, ConnectionEvent::SubsystemChange(Subsystem::Player));
+                }
+            },
+        )
+    }
+
+    /// Creates a stream of the server status, polled every `period`.
+    ///
+    /// Polling pauses while disconnected and resumes once reconnected.
+    /// Failed polls are skipped rather than yielded.
+    /// The stream ends once the client is shut down.
+    pub fn status_stream(&self, period: Duration) -> impl Stream<Item = Status> + Send + 'static {
+        let client = self.clone();
+
+        stream::unfold((client, None), move |(client, ticker)| async move {
+            // The timer can only be created within the runtime, so is created on the first poll.
+            let mut ticker = ticker.unwrap_or_else(|| {
+                let mut ticker = interval(period);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                ticker
+            });
+
+            loop {
+                tokio::select! {
+                    biased;
+                    () = wait_for_shutdown(client.shutdown.subscribe()) => return None,
+                    _ = ticker.tick() => {}
+                }
+
+                let connection = client.wait_for_client_or_shutdown().await?;
+                if let Ok(status) = connection.command(commands::Status).await {
+                    return Some((status, (client, Some(ticker))));
+                }
+            }
+        })
+    }
+
+    /// Waits until the given subsystem has changed `count` times.
+    ///
+    /// If `timeout` is provided and elapses before all changes are observed,
+    /// an [`Error::Timeout`] is returned.
+    pub async fn wait_for_subsystem_changes(
+        &self,
+        subsystem: Subsystem,
+        count: usize,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        let mut rx = self.subscribe();
+
+        let wait = async move {
+            let mut changes = 0;
+
+            while changes < count {
+                match rx.recv().await {
+                    Ok(event) => {
+                        if matches!(&*event, ConnectionEvent::SubsystemChange(changed) if changed == &subsystem)
+                        {
+                            changes += 1;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        };
+
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, wait)
+                .await
+                .map_err(|_| Error::Timeout),
+            None => {
+                wait.await;
+                Ok(())
+            }
+        }
+    }
+
+    /// Creates a new receiver for connection state changes.
+    ///
+    /// Only changes after subscribing are received,
+    /// so use [`PersistentClient::is_connected`] to get the current state.
+    pub fn subscribe_state(&self) -> broadcast::Receiver<ConnectionState> {
+        self.state_channel.subscribe()
+    }
+
+    /// Creates a new receiver for the current volume.
+    ///
+    /// This is updated on connect and each time the mixer changes.
+    /// The value is `None` when disconnected or the server has no mixer.
+    pub fn volume_watch(&self) -> watch::Receiver<Option<u8>> {
+        self.volume_channel.subscribe()
+    }
+
+    /// Creates a new receiver for player events,
+    /// which are derived from the status each time the player changes.
+    ///
+    /// Unlike the raw subsystem events, these distinguish playback
+    /// being stopped by a client from the queue running out.
+    pub fn player_events(&self) -> broadcast::Receiver<PlayerEvent> {
+        self.player_channel.subscribe()
+    }
+
+    /// Resolves the next time the queue becomes empty,
+    /// such as when the last song is removed by `consume` mode.
+    ///
+    /// Only changes after this is called are observed.
+    pub fn on_queue_empty(&self) -> impl Future<Output = ()> {
+        let mut rx = self.queue_empty_channel.subscribe();
+
+        async move {
+            // Lagging behind still means the queue became empty.
+            // The channel only closes once the client is dropped, so never resolve.
+            if let Err(RecvError::Closed) = rx.recv().await {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
     /// Runs the provided command on the MPD server.
     ///
     /// Waits for a valid connection and response before the future is completed.
     pub async fn command<C: Command>(&self, cmd: C) -> Result<C::Response, CommandError> {
-        self.with_client(|client| async move { client.command(cmd).await })
+        self.with_connection(|client| async move { client.command(cmd).await })
             .await
     }
 
-    /// Runs the `status` command on the MPD server.
+    /// Runs the provided commands on the MPD server as a single command list.
+    ///
+    /// The commands are executed atomically, in one round-trip,
+    /// and the responses are returned in the same order.
+    /// This accepts a tuple of commands, or a `Vec` of commands of the same type.
     ///
     /// Waits for a valid connection and response before the future is completed.
-    pub async fn status(&self) -> Result<Status, CommandError> {
-        self.command(commands::Status).await
+    /// The whole list is sent on the same connection.
+    pub async fn command_list<L: CommandList>(&self, list: L) -> Result<L::Response, CommandError> {
+        self.with_connection(|client| async move { client.command_list(list).await })
+            .await
     }
 
-    /// Runs the `currentsong` command on the MPD server.
+    /// Runs the `subscribe` command on the MPD server,
+    /// subscribing to the client-to-client message channel `channel`.
+    ///
+    /// The subscription is kept across reconnections.
     ///
     /// Waits for a valid connection and response before the future is completed.
-    pub async fn current_song(&self) -> Result<Option<SongInQueue>, CommandError> {
-        self.command(commands::CurrentSong).await
+    pub async fn subscribe_to_channel(&self, channel: &str) -> Result<(), CommandError> {
+        self.command(commands::SubscribeToChannel(channel)).await?;
+
+        self.channels
+            .lock()
+            .expect("Failed to get lock on channels")
+            .insert(channel.to_string());
+
+        Ok(())
     }
-}
 
-/// Creates a new client on the default localhost TCP address
+    /// Runs the `unsubscribe` command on the MPD server,
+    /// unsubscribing from the client-to-client message channel `channel`.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn unsubscribe_from_channel(&self, channel: &str) -> Result<(), CommandError> {
+        self.channels
+            .lock()
+            .expect("Failed to get lock on channels")
+            .remove(channel);
+
+        self.command(commands::UnsubscribeFromChannel(channel))
+            .await
+    }
+
+    /// Runs the provided command on the MPD server
+    /// if currently connected.
+    ///
+    /// Unlike [`PersistentClient::command`], this does not wait for a connection,
+    /// and instead returns [`Error::NotConnected`] immediately.
+    pub async fn try_command<C: Command>(&self, cmd: C) -> crate::Result<C::Response> {
+        let client = self.current_client().ok_or(Error::NotConnected)?;
+        client.command(cmd).await.map_err(Error::CommandError)
+    }
+
+    /// Gets the connected client, if any.
+    fn current_client(&self) -> Option<Arc<Client>> {
+        self.activity.touch();
+
+        match &*self.state.read() {
+            State::Connected(client) => Some(client.clone()),
+            State::Disconnected | State::Failed(_) => None,
+        }
+    }
+
+    /// Runs the provided command on the MPD server,
+    /// returning [`Error::Timeout`] if no response is received within `timeout`.
+    ///
+    /// The timeout includes waiting for a connection.
+    /// If the command itself times out, the connection is assumed dead,
+    /// so it is dropped and re-established.
+    pub async fn command_with_timeout<C: Command>(
+        &self,
+        cmd: C,
+        timeout: Duration,
+    ) -> crate::Result<C::Response> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        let client = tokio::time::timeout_at(deadline, self.wait_for_client())
+            .await
+            .map_err(|_| Error::Timeout)??;
+
+        match tokio::time::timeout_at(deadline, client.command(cmd)).await {
+            Ok(res) => res.map_err(Error::CommandError),
+            Err(_) => {
+                error!("Command to '{}' timed out, reconnecting", self.host());
+                self.reconnect();
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    /// Runs the `status` command on the MPD server.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn status(&self) -> Result<Status, CommandError> {
+        self.command(commands::Status).await
+    }
+
+    /// Runs the `currentsong` command on the MPD server.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn current_song(&self) -> Result<Option<SongInQueue>, CommandError> {
+        self.command(commands::CurrentSong).await
+    }
+
+    /// Gets the current song and status.
+    ///
+    /// Both are read in a single command list,
+    /// so the song cannot change between them.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn now_playing(&self) -> Result<NowPlaying, CommandError> {
+        let (status, song) = self
+            .command_list((commands::Status, commands::CurrentSong))
+            .await?;
+
+        Ok(NowPlaying { song, status })
+    }
+
+    /// Gets the queue position and id of the current song from the status.
+    ///
+    /// This is cheaper than [`PersistentClient::current_song`]
+    /// when the song metadata is not needed.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn current_song_ref(&self) -> Result<Option<(usize, SongId)>, CommandError> {
+        self.status()
+            .await
+            .map(|status| status.current_song.map(|(position, id)| (position.0, id)))
+    }
+
+    /// Gets the repeat, random, single and consume options from the status.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn playback_options(&self) -> Result<PlaybackOptions, CommandError> {
+        self.status()
+            .await
+            .map(|status| PlaybackOptions::from(&status))
+    }
+
+    /// Gets the current playback error from the status, if any.
+    ///
+    /// The server keeps the error until a song plays successfully,
+    /// or it is explicitly cleared.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn current_error(&self) -> Result<Option<String>, CommandError> {
+        self.status().await.map(|status| status.error)
+    }
+
+    /// Gets the time until the current song finishes playing.
+    ///
+    /// Returns `None` when stopped,
+    /// or when the song duration is unknown, such as for streams.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn time_remaining(&self) -> Result<Option<Duration>, CommandError> {
+        self.status().await.map(|status| time_remaining(&status))
+    }
+
+    /// Runs the `clearerror` command on the MPD server,
+    /// clearing the current playback error.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn clear_error(&self) -> Result<(), CommandError> {
+        self.command(ClearError).await
+    }
+
+    /// Gets the partition the connection is bound to from the status.
+    ///
+    /// This is read from the server each time,
+    /// so it always reflects the current partition after switching.
+    /// Returns `None` for servers without partition support.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn current_partition(&self) -> Result<Option<String>, CommandError> {
+        self.command(CurrentPartition).await
+    }
+
+    /// Runs the `partition` command on the MPD server,
+    /// switching to the partition `name`.
+    ///
+    /// The partition is kept across reconnections.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn partition(&self, name: &str) -> Result<(), CommandError> {
+        self.command(Partition(name)).await?;
+
+        *self
+            .partition
+            .lock()
+            .expect("Failed to get lock on partition") = Some(name.to_string());
+
+        Ok(())
+    }
+
+    /// Gets the current version of the queue.
+    /// This is incremented by the server each time the queue changes.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn queue_version(&self) -> Result<u32, CommandError> {
+        self.status().await.map(|status| status.playlist_version)
+    }
+
+    /// Runs the `plchanges` command on the MPD server,
+    /// getting the songs in the queue which changed since `since_version`.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn queue_changes(
+        &self,
+        since_version: u32,
+    ) -> Result<Vec<SongInQueue>, CommandError> {
+        self.command(QueueChanges(since_version)).await
+    }
+
+    /// Gets the metadata for each of the songs at `uris`,
+    /// in the same order.
+    ///
+    /// Songs which do not exist in the database are returned as `None`.
+    /// All lookups are sent as a single command list.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn songs_info(&self, uris: &[&str]) -> Result<Vec<Option<Song>>, CommandError> {
+        // `find` is used over `lsinfo`, as it returns an empty response for missing files
+        // rather than an error, which would abort the rest of the list.
+        let list = uris
+            .iter()
+            .map(|uri| commands::Find::new(Filter::tag(Tag::Other("file".into()), *uri)))
+            .collect::<Vec<_>>();
+
+        let songs = self.command_list(list).await?;
+
+        Ok(songs
+            .into_iter()
+            .map(|songs| songs.into_iter().next())
+            .collect())
+    }
+
+    /// Starts or resumes playback of the current song.
+    ///
+    /// When stopped, this plays from the current position in the queue.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn play(&self) -> crate::Result<()> {
+        self.command(commands::Play::current())
+            .await
+            .map_err(Error::CommandError)
+    }
+
+    /// Stops playback.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn stop(&self) -> crate::Result<()> {
+        self.command(commands::Stop)
+            .await
+            .map_err(Error::CommandError)
+    }
+
+    /// Pauses playback if playing, or resumes it if paused.
+    ///
+    /// When stopped, this starts playing the current song,
+    /// or returns [`Error::NotPlaying`] if strict controls are enabled.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn toggle_pause(&self) -> crate::Result<()> {
+        let status = self.status().await?;
+
+        match status.state {
+            PlayState::Playing => self.command(commands::SetPause(true)).await,
+            PlayState::Paused => self.command(commands::SetPause(false)).await,
+            PlayState::Stopped if self.strict_controls => return Err(Error::NotPlaying),
+            PlayState::Stopped => self.command(commands::Play::current()).await,
+        }
+        .map_err(Error::CommandError)
+    }
+
+    /// Skips to the next song in the queue.
+    ///
+    /// When stopped, this starts playing the first song in the queue,
+    /// or returns [`Error::NotPlaying`] if strict controls are enabled.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn next(&self) -> crate::Result<()> {
+        if self.is_active().await? {
+            self.command(commands::Next).await
+        } else {
+            self.command(commands::Play::song(SongPosition(0))).await
+        }
+        .map_err(Error::CommandError)
+    }
+
+    /// Goes back to the previous song in the queue.
+    ///
+    /// When stopped, this does nothing,
+    /// or returns [`Error::NotPlaying`] if strict controls are enabled.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn previous(&self) -> crate::Result<()> {
+        if self.is_active().await? {
+            self.command(commands::Previous).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Pauses playback.
+    ///
+    /// When stopped, this does nothing,
+    /// or returns [`Error::NotPlaying`] if strict controls are enabled.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn pause(&self) -> crate::Result<()> {
+        if self.is_active().await? {
+            self.command(commands::SetPause(true)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Seeks to `position` in the current song.
+    /// Positions past the end of the song are clamped to its duration.
+    ///
+    /// When stopped, this returns [`Error::NotPlaying`]
+    /// whether or not strict controls are enabled,
+    /// as there is no song to seek in.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn seek(&self, position: Duration) -> crate::Result<()> {
+        let status = self.active_status().await?.ok_or(Error::NotPlaying)?;
+        self.seek_within(position, status.duration).await
+    }
+
+    /// Seeks forwards or backwards by `delta` seconds in the current song.
+    /// The new position is clamped between the start and end of the song.
+    ///
+    /// When stopped, this returns [`Error::NotPlaying`]
+    /// whether or not strict controls are enabled,
+    /// as there is no song to seek in.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn seek_relative(&self, delta: i64) -> crate::Result<()> {
+        let status = self.active_status().await?.ok_or(Error::NotPlaying)?;
+        let elapsed = status.elapsed.unwrap_or_default();
+
+        self.seek_within(offset_position(elapsed, delta), status.duration)
+            .await
+    }
+
+    /// Seeks to `position`, clamped to `duration` if it is known.
+    async fn seek_within(
+        &self,
+        position: Duration,
+        duration: Option<Duration>,
+    ) -> crate::Result<()> {
+        let position = duration.map_or(position, |duration| position.min(duration));
+
+        self.command(commands::Seek(SeekMode::Absolute(position)))
+            .await
+            .map_err(Error::CommandError)
+    }
+
+    /// Gets the current volume as a percentage,
+    /// or `None` if the server has no mixer.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn volume(&self) -> crate::Result<Option<u8>> {
+        self.command(Volume).await.map_err(Error::CommandError)
+    }
+
+    /// Sets the volume, clamped to the range `0..=100`.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn set_volume(&self, percent: u8) -> crate::Result<()> {
+        self.command(commands::SetVolume(percent.min(100)))
+            .await
+            .map_err(Error::CommandError)
+    }
+
+    /// Increases the volume by `delta`, up to `100`.
+    ///
+    /// Servers without a mixer are left unchanged.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn increase_volume(&self, delta: u8) -> crate::Result<()> {
+        self.adjust_volume(i16::from(delta)).await
+    }
+
+    /// Decreases the volume by `delta`, down to `0`.
+    ///
+    /// Servers without a mixer are left unchanged.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn decrease_volume(&self, delta: u8) -> crate::Result<()> {
+        self.adjust_volume(-i16::from(delta)).await
+    }
+
+    /// Adjusts the volume by `delta`, clamped to the range `0..=100`.
+    ///
+    /// Servers without a mixer are left unchanged.
+    pub(crate) async fn adjust_volume(&self, delta: i16) -> crate::Result<()> {
+        let Some(volume) = self.volume().await? else {
+            return Ok(());
+        };
+
+        let volume = i16::from(volume).saturating_add(delta).clamp(0, 100) as u8;
+        self.set_volume(volume).await
+    }
+
+    /// Checks whether the player is playing or paused.
+    ///
+    /// Returns [`Error::NotPlaying`] when stopped if strict controls are enabled.
+    async fn is_active(&self) -> crate::Result<bool> {
+        self.active_status().await.map(|status| status.is_some())
+    }
+
+    /// Gets the status if the player is playing or paused.
+    ///
+    /// Returns [`Error::NotPlaying`] when stopped if strict controls are enabled.
+    async fn active_status(&self) -> crate::Result<Option<Status>> {
+        let status = self.status().await?;
+
+        match status.state {
+            PlayState::Playing | PlayState::Paused => Ok(Some(status)),
+            PlayState::Stopped if self.strict_controls => Err(Error::NotPlaying),
+            PlayState::Stopped => Ok(None),
+        }
+    }
+
+    /// Runs the `seekid` command on the MPD server,
+    /// playing the queued song with the given id from `pos`.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn seek_id(&self, id: SongId, pos: Duration) -> crate::Result<()> {
+        self.command(commands::SeekTo(id.into(), pos))
+            .await
+            .map_err(Error::CommandError)
+    }
+
+    /// Replaces the queue with the given songs,
+    /// optionally starting playback from the first one.
+    ///
+    /// This is sent as a single command list,
+    /// so the queue is never observed as empty.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn replace_queue(&self, uris: &[&str], play: bool) -> Result<(), CommandError> {
+        let mut list = RawCommandList::new(commands::ClearQueue.command());
+
+        for uri in uris {
+            list.add(commands::Add::uri(uri).command());
+        }
+
+        if play && !uris.is_empty() {
+            list.add(commands::Play::song(SongPosition(0)).command());
+        }
+
+        self.with_connection(|client| async move { client.raw_command_list(list).await })
+            .await
+            .map(|_| ())
+    }
+
+    /// Adds the directory at `path` and everything under it to the queue,
+    /// returning the number of songs which were added.
+    ///
+    /// The count is taken from the queue length before and after adding,
+    /// within a single command list.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn add_dir(&self, path: &str) -> Result<usize, CommandError> {
+        let (before, (), after) = self
+            .command_list((commands::Status, AddUri(path), commands::Status))
+            .await?;
+
+        Ok(after.playlist_length.saturating_sub(before.playlist_length))
+    }
+
+    /// Runs the `decoders` command on the MPD server,
+    /// listing the available decoder plugins.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn decoders(&self) -> Result<Vec<Decoder>, CommandError> {
+        self.command(Decoders).await
+    }
+
+    /// Runs the `outputset` command on the MPD server,
+    /// setting the runtime attribute `name` to `value` on the output with the given id.
+    ///
+    /// Servers older than MPD 0.21 do not support this,
+    /// and return an error response with the code `5` (unknown command).
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn set_output_attribute(
+        &self,
+        id: u32,
+        name: &str,
+        value: &str,
+    ) -> Result<(), CommandError> {
+        self.command(OutputSet { id, name, value }).await
+    }
+
+    /// Runs the `config` command on the MPD server,
+    /// getting the configured music directory.
+    ///
+    /// The server only allows this over a local socket connection.
+    /// If the connection is not local, or no music directory is configured,
+    /// this returns `None`.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn music_directory(&self) -> Result<Option<PathBuf>, CommandError> {
+        match self.command(Config).await {
+            Err(CommandError::ErrorResponse { error, .. })
+                if error.code == ACK_ERROR_PERMISSION =>
+            {
+                Ok(None)
+            }
+            res => res,
+        }
+    }
+
+    /// Runs the `readcomments` command on the MPD server,
+    /// getting the raw metadata comments embedded in the file at `uri`.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn comments(&self, uri: &str) -> Result<Vec<(String, String)>, CommandError> {
+        self.command(ReadComments(uri)).await
+    }
+
+    /// Runs the `sticker get` command on the MPD server,
+    /// getting the value of the sticker `name` on the song at `uri`.
+    ///
+    /// Returns `None` if the sticker is not set.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn sticker(&self, uri: &str, name: &str) -> Result<Option<String>, CommandError> {
+        match self.command(commands::StickerGet::new(uri, name)).await {
+            Ok(sticker) => Ok(Some(sticker.value)),
+            Err(CommandError::ErrorResponse { error, .. }) if error.code == ACK_ERROR_NO_EXIST => {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Gets the lyrics for the song at `uri`.
+    ///
+    /// This checks, in order:
+    ///
+    /// - The `lyrics` sticker on the song
+    /// - A `.lrc` or `.txt` file next to the song,
+    ///   if the music directory is available (see [`PersistentClient::music_directory`])
+    ///   and `uri` is a relative path within it
+    ///
+    /// Returns `None` if no lyrics are found.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn lyrics(&self, uri: &str) -> Result<Option<String>, CommandError> {
+        match self.sticker(uri, LYRICS_STICKER).await {
+            Ok(Some(lyrics)) => return Ok(Some(lyrics)),
+            // The sticker database may be disabled on the server.
+            Ok(None) | Err(CommandError::ErrorResponse { .. }) => {}
+            Err(err) => return Err(err),
+        }
+
+        let Some(music_directory) = self.music_directory().await? else {
+            return Ok(None);
+        };
+
+        let Some(path) = song_path(&music_directory, uri) else {
+            return Ok(None);
+        };
+
+        for extension in LYRICS_EXTENSIONS {
+            if let Ok(lyrics) = tokio::fs::read_to_string(path.with_extension(extension)).await {
+                return Ok(Some(lyrics));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Gets the album art for the song at `uri`.
+    ///
+    /// This prefers art embedded in the file using `readpicture`,
+    /// falling back to a cover file in the song's directory using `albumart`.
+    /// The art is sent by the server in chunks,
+    /// which are requested in turn and joined together.
+    ///
+    /// Returns `None` if the song has no album art.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn album_art(&self, uri: &str) -> Result<Option<Vec<u8>>, CommandError> {
+        let art = self
+            .with_connection(|client| async move { client.album_art(uri).await })
+            .await?;
+
+        Ok(art.map(|(data, _mime)| data.to_vec()))
+    }
+
+    /// Gets the album art for the song at `uri`,
+    /// using the art cache if one is configured
+    /// (see [`PersistentClientBuilder::art_cache`]).
+    ///
+    /// Art is only fetched from the server the first time it is requested,
+    /// including for songs without any art.
+    /// Without a cache, this is the same as [`PersistentClient::album_art`].
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn album_art_cached(&self, uri: &str) -> Result<Art, CommandError> {
+        let Some(cache) = &self.art_cache else {
+            return Ok(self.album_art(uri).await?.map(Arc::from));
+        };
+
+        if let Some(art) = cache
+            .lock()
+            .expect("Failed to get lock on art cache")
+            .get(uri)
+        {
+            return Ok(art);
+        }
+
+        let art: Art = self.album_art(uri).await?.map(Arc::from);
+        cache
+            .lock()
+            .expect("Failed to get lock on art cache")
+            .insert(uri.to_string(), art.clone());
+
+        Ok(art)
+    }
+
+    /// Removes all album art from the art cache.
+    pub fn clear_art_cache(&self) {
+        if let Some(cache) = &self.art_cache {
+            cache
+                .lock()
+                .expect("Failed to get lock on art cache")
+                .clear();
+        }
+    }
+
+    /// Runs the `playlistmove` command on the MPD server,
+    /// moving the song at `from` in the stored playlist `name` to `to`.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn playlist_move(
+        &self,
+        name: &str,
+        from: usize,
+        to: usize,
+    ) -> Result<(), CommandError> {
+        self.command(commands::MoveInPlaylist::new(name, from, to))
+            .await
+    }
+
+    /// Runs the `playlistdelete` command on the MPD server,
+    /// removing the song at `pos` from the stored playlist `name`.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn playlist_delete(&self, name: &str, pos: usize) -> Result<(), CommandError> {
+        self.command(commands::RemoveFromPlaylist::position(name, pos))
+            .await
+    }
+
+    /// Runs the `playlistclear` command on the MPD server,
+    /// removing all songs from the stored playlist `name`.
+    ///
+    /// Waits for a valid connection and response before the future is completed.
+    pub async fn playlist_clear(&self, name: &str) -> Result<(), CommandError> {
+        self.command(commands::ClearPlaylist(name)).await
+    }
+}
+
+/// Gets the path of the song at `uri` within the music directory.
+///
+/// Returns `None` if `uri` is absolute or contains `..`,
+/// as it could then point outside the music directory.
+fn song_path(music_directory: &Path, uri: &str) -> Option<PathBuf> {
+    let uri = Path::new(uri);
+    let relative = uri
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+    relative.then(|| music_directory.join(uri))
+}
+
+/// Checks whether the error was caused by the connection being lost,
+/// rather than by the command itself.
+fn is_connection_lost(err: &CommandError) -> bool {
+    // Protocol errors, including IO errors, always close the connection.
+    matches!(
+        err,
+        CommandError::ConnectionClosed | CommandError::Protocol(_)
+    )
+}
+
+/// Resolves once the shutdown flag is set,
+/// or the client holding the sender is dropped.
+async fn wait_for_shutdown(mut shutdown: watch::Receiver<bool>) {
+    // An error means the sender was dropped, which is treated as a shutdown.
+    let _ = shutdown.wait_for(|&shutdown| shutdown).await;
+}
+
+/// Error from a single connection attempt.
+#[derive(Debug)]
+enum ConnectError {
+    Protocol(MpdProtocolError),
+    /// The server rejected the password.
+    Password(CommandError),
+    /// The attempt did not complete within the connect timeout.
+    Timeout,
+}
+
+impl Display for ConnectError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectError::Protocol(err) => write!(f, "{err:?}"),
+            ConnectError::Password(err) => write!(f, "Password rejected: {err}"),
+            ConnectError::Timeout => write!(f, "Timed out"),
+        }
+    }
+}
+
+/// Attempts to connect to each host in order,
+/// returning the first successful connection with the index of its host,
+/// or the error from the last host if none connect.
+async fn connect_any(
+    hosts: &[String],
+    connector: &dyn Connector,
+    password: Option<&Password>,
+    connect_timeout: Duration,
+) -> Result<(usize, Connection), ConnectError> {
+    let mut last_err = None;
+
+    for (index, host) in hosts.iter().enumerate() {
+        debug!("Attempting to connect to {host}");
+
+        let attempt = tokio::time::timeout(connect_timeout, connect(host, connector, password));
+
+        match attempt.await.unwrap_or(Err(ConnectError::Timeout)) {
+            Ok(connection) => return Ok((index, connection)),
+            Err(err) => {
+                debug!("Failed to connect to '{host}': {err}");
+                last_err = Some(err);
+            }
+        }
+    }
+
+    Err(last_err.expect("At least one address is required"))
+}
+
+/// Connects to the host, and authenticates if a password is set.
+async fn connect(
+    host: &str,
+    connector: &dyn Connector,
+    password: Option<&Password>,
+) -> Result<Connection, ConnectError> {
+    let connection = connector
+        .connect(host)
+        .await
+        .map_err(ConnectError::Protocol)?;
+
+    if let Some(password) = password {
+        connection
+            .0
+            .command(password.clone())
+            .await
+            .map_err(ConnectError::Password)?;
+    }
+
+    Ok(connection)
+}
+
+type ConnectHook = Arc<dyn Fn(Arc<Client>) + Send + Sync>;
+type DisconnectHook = Arc<dyn Fn() + Send + Sync>;
+
+/// Callbacks registered with [`PersistentClient::on_connect`]
+/// and [`PersistentClient::on_disconnect`].
+#[derive(Default)]
+struct Hooks {
+    connect: Mutex<Vec<ConnectHook>>,
+    disconnect: Mutex<Vec<DisconnectHook>>,
+}
+
+impl Hooks {
+    fn connected(&self, client: &Arc<Client>) {
+        // The callbacks are cloned out of the lock,
+        // so that they can register further callbacks.
+        let hooks = self
+            .connect
+            .lock()
+            .expect("Failed to get lock on hooks")
+            .clone();
+
+        for hook in hooks {
+            hook(client.clone());
+        }
+    }
+
+    fn disconnected(&self) {
+        let hooks = self
+            .disconnect
+            .lock()
+            .expect("Failed to get lock on hooks")
+            .clone();
+
+        for hook in hooks {
+            hook();
+        }
+    }
+}
+
+/// Runs the disconnect callbacks when dropped.
+struct OnDisconnect<'a>(&'a Hooks);
+
+impl Drop for OnDisconnect<'_> {
+    fn drop(&mut self) {
+        self.0.disconnected();
+    }
+}
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Hooks")
+    }
+}
+
+/// Publishes connection state changes,
+/// skipping repeats of the same state.
+struct StatePublisher {
+    tx: broadcast::Sender<ConnectionState>,
+    last: Option<ConnectionState>,
+}
+
+impl StatePublisher {
+    fn new(tx: broadcast::Sender<ConnectionState>) -> Self {
+        Self { tx, last: None }
+    }
+
+    fn publish(&mut self, state: ConnectionState) {
+        if self.last.replace(state) != Some(state) {
+            debug!("Connection state changed: {state:?}");
+
+            // Sending only fails when there are no receivers, which is fine.
+            let _ = self.tx.send(state);
+        }
+    }
+}
+
+/// Tracks consecutive connection failures
+/// to log a summary at an increasing interval,
+/// rather than logging every failed attempt.
+#[derive(Debug, Default)]
+struct FailureLog {
+    attempts: u32,
+    since: Option<Instant>,
+}
+
+impl FailureLog {
+    /// Records a failed attempt,
+    /// logging on the first failure and then each time the count doubles.
+    fn record(&mut self, hosts: &[String], err: &ConnectError) {
+        let since = *self.since.get_or_insert_with(Instant::now);
+        self.attempts += 1;
+
+        let hosts = hosts.join("', '");
+
+        if self.attempts == 1 {
+            error!("Failed to connect to '{hosts}': {err}");
+        } else if self.attempts.is_power_of_two() {
+            error!(
+                "Still failing to connect to '{hosts}' ({} attempts over {:?}): {err}",
+                self.attempts,
+                since.elapsed()
+            );
+        }
+    }
+
+    /// Resets the count after a successful connection.
+    fn reset(&mut self) {
+        if self.attempts > 1 {
+            info!("Reconnected after {} failed attempts", self.attempts);
+        }
+
+        *self = Self::default();
+    }
+}
+
+/// Switches a new connection to the partition, if one is set.
+async fn select_partition(client: &Client, partition: &Mutex<Option<String>>) {
+    let partition = partition
+        .lock()
+        .expect("Failed to get lock on partition")
+        .clone();
+
+    if let Some(partition) = partition {
+        if let Err(err) = client.command(Partition(&partition)).await {
+            error!("Failed to select partition '{partition}': {err:?}");
+        }
+    }
+}
+
+/// Re-subscribes to the tracked message channels on a new connection.
+async fn resubscribe(client: &Client, channels: &Mutex<HashSet<String>>) {
+    let channels = channels
+        .lock()
+        .expect("Failed to get lock on channels")
+        .clone();
+
+    for channel in channels {
+        if let Err(err) = client.command(commands::SubscribeToChannel(&channel)).await {
+            error!("Failed to re-subscribe to channel '{channel}': {err:?}");
+        }
+    }
+}
+
+/// Forwards subsystem change events from the server.
+///
+/// If the server rejects `idle`, `no_idle` is set
+/// so that later connections poll the status instead.
+///
+/// Returns once the connection is lost.
+async fn watch_events(
+    host: &str,
+    events: &mut ConnectionEvents,
+    no_idle: &AtomicBool,
+    tx: &broadcast::Sender<Arc<ConnectionEvent>>,
+    derived: &mut Derived<'_>,
+) {
+    while let Some(event) = events.next().await {
+        match event {
+            // This is only sent when `idle` or `noidle` returns an error.
+            ConnectionEvent::ConnectionClosed(ConnectionError::InvalidResponse) => {
+                warn!("'{host}' rejected idle, falling back to polling the status");
+                no_idle.store(true, Ordering::Relaxed);
+                return;
+            }
+            ConnectionEvent::ConnectionClosed(err) => {
+                error!("Lost connection to '{host}': {err:?}");
+                return;
+            }
+            ConnectionEvent::SubsystemChange(subsystem) => {
+                debug!("Sending event: {subsystem:?}");
+
+                // Wrap in `Arc` because `ConnectionEvent` isn't `Clone`.
+                tx.send(Arc::new(ConnectionEvent::SubsystemChange(
+                    subsystem.clone(),
+                )))
+                .expect("Failed to send event");
+
+                derived.update(&subsystem).await;
+            }
+        }
+    }
+}
+
+/// Tracks use of the client, to close the connection when idle.
+#[derive(Debug, Default)]
+struct Activity {
+    /// Notified on each command.
+    touched: Notify,
+    /// Notified to reconnect once idle.
+    wake: Notify,
+    /// Whether the connection was closed for being idle.
+    idle: AtomicBool,
+}
+
+impl Activity {
+    /// Records use of the client, reconnecting if it is idle.
+    fn touch(&self) {
+        self.touched.notify_one();
+
+        if self.idle.load(Ordering::SeqCst) {
+            self.wake.notify_waiters();
+        }
+    }
+}
+
+/// Waits until no commands have been sent and no events received for `timeout`,
+/// or never if the idle timeout is disabled.
+async fn wait_for_idle(
+    activity: &Activity,
+    mut events: broadcast::Receiver<Arc<ConnectionEvent>>,
+    timeout: Option<Duration>,
+) {
+    let Some(timeout) = timeout else {
+        return std::future::pending().await;
+    };
+
+    loop {
+        tokio::select! {
+            () = sleep(timeout) => return,
+            () = activity.touched.notified() => {}
+            _ = events.recv() => {}
+        }
+    }
+}
+
+/// Pings the server on each interval.
+///
+/// Returns once a ping fails or does not respond within the interval,
+/// or never if the heartbeat is disabled.
+async fn heartbeat(client: &Client, interval: Option<Duration>) {
+    let Some(interval) = interval else {
+        return std::future::pending().await;
+    };
+
+    let mut ticks = tokio::time::interval(interval);
+    // The first tick completes immediately, and the connection was only just made.
+    ticks.tick().await;
+
+    loop {
+        ticks.tick().await;
+
+        match tokio::time::timeout(interval, client.command(commands::Ping)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => {
+                debug!("Heartbeat failed: {err:?}");
+                return;
+            }
+            Err(_) => {
+                debug!("Heartbeat timed out");
+                return;
+            }
+        }
+    }
+}
+
+/// Synthesizes subsystem change events by polling the server status,
+/// for servers which do not report changes through `idle`.
+///
+/// Returns once the connection is lost.
+async fn poll_status(
+    host: &str,
+    client: &Client,
+    events: &mut ConnectionEvents,
+    poll_interval: Duration,
+    tx: &broadcast::Sender<Arc<ConnectionEvent>>,
+    derived: &mut Derived<'_>,
+) {
+    let mut interval = tokio::time::interval(poll_interval);
+    let mut previous: Option<Status> = None;
+
+    loop {
+        tokio::select! {
+            event = events.next() => match event {
+                Some(ConnectionEvent::ConnectionClosed(err)) => {
+                    error!("Lost connection to '{host}': {err:?}");
+                    return;
+                }
+                // Changes are synthesized from the status instead.
+                Some(ConnectionEvent::SubsystemChange(_)) => {}
+                None => return,
+            },
+            _ = interval.tick() => match client.command(commands::Status).await {
+                Ok(status) => {
+                    if let Some(previous) = &previous {
+                        for subsystem in status_changes(previous, &status) {
+                            debug!("Sending synthesized event: {subsystem:?}");
+
+                            tx.send(Arc::new(ConnectionEvent::SubsystemChange(subsystem.clone())))
+                                .expect("Failed to send event");
+
+                            derived.update(&subsystem).await;
+                        }
+                    }
+
+                    previous = Some(status);
+                }
+                Err(err) => {
+                    error!("Lost connection to '{host}': {err:?}");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// State derived from the events of a single connection.
+struct Derived<'a> {
+    client: &'a Client,
+    player: PlayerTracker,
+    player_tx: &'a broadcast::Sender<PlayerEvent>,
+    queue_length: Option<usize>,
+    queue_empty_tx: &'a broadcast::Sender<()>,
+    volume_tx: &'a watch::Sender<Option<u8>>,
+}
+
+impl Derived<'_> {
+    /// Sets the initial derived state after connecting.
+    async fn init(&mut self) {
+        if let Ok(status) = self.client.command(commands::Status).await {
+            self.queue_length = Some(status.playlist_length);
+            self.player.update(status);
+        }
+
+        self.update_volume().await;
+    }
+
+    /// Updates the derived state after a subsystem change.
+    async fn update(&mut self, subsystem: &Subsystem) {
+        match subsystem {
+            Subsystem::Player => self.update_player().await,
+            Subsystem::Mixer => self.update_volume().await,
+            Subsystem::Queue => self.update_queue().await,
+            _ => {}
+        }
+    }
+
+    async fn update_player(&mut self) {
+        match self.client.command(commands::Status).await {
+            Ok(status) => {
+                for event in self.player.update(status) {
+                    debug!("Sending player event: {event:?}");
+
+                    // Sending only fails when there are no receivers, which is fine.
+                    let _ = self.player_tx.send(event);
+                }
+            }
+            Err(err) => error!("Failed to get status: {err:?}"),
+        }
+    }
+
+    async fn update_queue(&mut self) {
+        match self.client.command(commands::Status).await {
+            Ok(status) => {
+                let previous = self.queue_length.replace(status.playlist_length);
+
+                if status.playlist_length == 0 && previous.is_some_and(|length| length > 0) {
+                    debug!("Queue emptied");
+
+                    // Sending only fails when there are no receivers, which is fine.
+                    let _ = self.queue_empty_tx.send(());
+                }
+            }
+            Err(err) => error!("Failed to get status: {err:?}"),
+        }
+    }
+
+    async fn update_volume(&self) {
+        match self.client.command(Volume).await {
+            Ok(volume) => {
+                self.volume_tx.send_if_modified(|current| {
+                    let modified = *current != volume;
+                    *current = volume;
+                    modified
+                });
+            }
+            Err(err) => error!("Failed to get volume: {err:?}"),
+        }
+    }
+}
+
+/// Gets the time until the current song finishes playing.
+fn time_remaining(status: &Status) -> Option<Duration> {
+    if status.state == PlayState::Stopped {
+        return None;
+    }
+
+    match (status.elapsed, status.duration) {
+        (Some(elapsed), Some(duration)) => Some(duration.saturating_sub(elapsed)),
+        _ => None,
+    }
+}
+
+/// Offsets `position` by `delta` seconds,
+/// without going before the start of the song.
+fn offset_position(position: Duration, delta: i64) -> Duration {
+    let offset = Duration::from_secs(delta.unsigned_abs());
+
+    if delta.is_negative() {
+        position.saturating_sub(offset)
+    } else {
+        position.saturating_add(offset)
+    }
+}
+
+/// Gets the subsystems which have changed between two statuses.
+fn status_changes(previous: &Status, current: &Status) -> Vec<Subsystem> {
+    let mut changes = vec![];
+
+    if previous.state != current.state || previous.current_song != current.current_song {
+        changes.push(Subsystem::Player);
+    }
+
+    if previous.volume != current.volume {
+        changes.push(Subsystem::Mixer);
+    }
+
+    if previous.repeat != current.repeat
+        || previous.random != current.random
+        || previous.single != current.single
+        || previous.consume != current.consume
+        || previous.crossfade != current.crossfade
+    {
+        changes.push(Subsystem::Options);
+    }
+
+    if previous.playlist_version != current.playlist_version {
+        changes.push(Subsystem::Queue);
+    }
+
+    if previous.update_job != current.update_job {
+        changes.push(Subsystem::Update);
+    }
+
+    changes
+}
+
+impl Clone for PersistentClient {
+    fn clone(&self) -> Self {
+        Self {
+            hosts: self.hosts.clone(),
+            active_host: self.active_host.clone(),
+            local: self.local.clone(),
+            retry_strategy: self.retry_strategy.clone(),
+            connector: self.connector.clone(),
+            password: self.password.clone(),
+            connect_timeout: self.connect_timeout,
+            poll_interval: self.poll_interval,
+            no_idle: self.no_idle.clone(),
+            idle_hold: self.idle_hold.clone(),
+            heartbeat_interval: self.heartbeat_interval,
+            idle_timeout: self.idle_timeout,
+            activity: self.activity.clone(),
+            strict_controls: self.strict_controls,
+            max_attempts: self.max_attempts,
+            state: self.state.clone(),
+            stats: self.stats.clone(),
+            last_error: self.last_error.clone(),
+            // Each clone has its own receiver for `recv`.
+            channel: (self.channel.0.clone(), self.channel.1.resubscribe()),
+            connection_channel: (
+                self.connection_channel.0.clone(),
+                self.connection_channel.1.resubscribe(),
+            ),
+            player_channel: self.player_channel.clone(),
+            state_channel: self.state_channel.clone(),
+            queue_empty_channel: self.queue_empty_channel.clone(),
+            volume_channel: self.volume_channel.clone(),
+            channels: self.channels.clone(),
+            partition: self.partition.clone(),
+            adopted_connection: self.adopted_connection.clone(),
+            drop_connection: self.drop_connection.clone(),
+            retry_now: self.retry_now.clone(),
+            hooks: self.hooks.clone(),
+            art_cache: self.art_cache.clone(),
+            shutdown: self.shutdown.clone(),
+        }
+    }
+}
+
+impl MpdClient for PersistentClient {
+    fn command<'a, C>(&'a self, cmd: C) -> BoxFuture<'a, crate::Result<C::Response>>
+    where
+        C: Command + Send + 'a,
+        C::Response: Send,
+    {
+        Box::pin(async move { Ok(PersistentClient::command(self, cmd).await?) })
+    }
+
+    fn status(&self) -> BoxFuture<'_, crate::Result<Status>> {
+        Box::pin(async move { Ok(PersistentClient::status(self).await?) })
+    }
+
+    fn current_song(&self) -> BoxFuture<'_, crate::Result<Option<SongInQueue>>> {
+        Box::pin(async move { Ok(PersistentClient::current_song(self).await?) })
+    }
+
+    fn is_connected(&self) -> bool {
+        PersistentClient::is_connected(self)
+    }
+
+    fn event_stream(&self) -> BoxStream<'static, Arc<ConnectionEvent>> {
+        PersistentClient::event_stream(self).boxed()
+    }
+}
+
+/// Creates a new client on the default localhost TCP address
 /// with a connection retry of 5 seconds.
 impl Default for PersistentClient {
     fn default() -> Self {
-        PersistentClient::new("localhost:6600".to_string(), Duration::from_secs(5))
+        PersistentClientBuilder::default().build()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{offset_position, song_path};
+    use crate::test_utils::{FakeServer, Reply};
     use crate::*;
+    use futures::StreamExt;
     use mpd_client::commands;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
     #[tokio::test]
+    #[ignore = "requires a running MPD server"]
     async fn test() {
         let client = PersistentClient::default();
         client.init();
@@ -197,8 +2398,712 @@ mod tests {
         let status = client
             .with_client(|client| async move { client.command(commands::Status).await })
             .await
+            .unwrap()
             .unwrap();
 
         println!("{:?}", status);
     }
+
+    #[tokio::test]
+    async fn wait_for_client_or_shutdown() {
+        // Nothing listens on port 1, so the client never connects.
+        let client = PersistentClient::new("localhost:1".to_string(), Duration::from_secs(5));
+        client.init();
+        client.shutdown();
+
+        assert!(client.wait_for_client_or_shutdown().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn shutdown_stops_reconnecting() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_millis(10));
+
+        // Every connection is closed immediately, so the client keeps retrying.
+        let attempts = server.reject();
+        client.init();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client.shutdown();
+        assert!(!client.is_connected());
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let after_shutdown = attempts.load(Ordering::SeqCst);
+        assert!(after_shutdown > 0);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(attempts.load(Ordering::SeqCst), after_shutdown);
+    }
+
+    #[tokio::test]
+    async fn reconnect_skips_retry_delay() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_secs(60));
+
+        let attempts = server.reject();
+        client.init();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        client.reconnect();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert!(client.last_error().is_some());
+
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClientBuilder::new()
+            .host(server.addr())
+            .retry_interval(Duration::from_millis(10))
+            .max_attempts(2)
+            .build();
+
+        let attempts = server.reject();
+        let mut states = client.subscribe_state();
+        client.init();
+
+        let result = tokio::time::timeout(Duration::from_secs(5), client.wait_for_client())
+            .await
+            .expect("wait should not hang");
+
+        assert!(matches!(result, Err(Error::ConnectionFailed(_))));
+        assert!(client.has_failed());
+        assert!(!client.is_connected());
+        assert_eq!(states.recv().await.unwrap(), ConnectionState::Reconnecting);
+        assert_eq!(states.recv().await.unwrap(), ConnectionState::Failed);
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn init_handle_completes_on_shutdown() {
+        let client = PersistentClient::new("localhost:1".to_string(), Duration::from_secs(5));
+        let handle = client.init();
+        client.shutdown();
+
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("task should stop")
+            .expect("task should not panic");
+    }
+
+    #[tokio::test]
+    async fn clone_shares_connection() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_millis(50));
+        let clone = client.clone();
+
+        assert!(!client.is_connected());
+        assert!(!clone.is_connected());
+
+        server.serve();
+        client.init();
+
+        tokio::time::timeout(Duration::from_secs(5), clone.wait_for_client())
+            .await
+            .expect("clone should observe the connection")
+            .unwrap();
+        assert!(client.is_connected());
+        assert!(clone.is_connected());
+        assert!(clone.last_error().is_none());
+        assert!(clone.stats().last_connected.is_some());
+    }
+
+    #[tokio::test]
+    async fn subscribe_state_transitions() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_millis(50));
+        let mut states = client.subscribe_state();
+
+        server.serve();
+        client.init();
+
+        assert_eq!(states.recv().await.unwrap(), ConnectionState::Reconnecting);
+        assert_eq!(states.recv().await.unwrap(), ConnectionState::Connected);
+
+        client.shutdown();
+        assert_eq!(states.recv().await.unwrap(), ConnectionState::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn wait_for_disconnect_resolves() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_secs(60));
+
+        // Not connected yet, so this resolves immediately.
+        client.wait_for_disconnect().await;
+
+        server.serve();
+        client.init();
+        client.wait_for_client().await.unwrap();
+
+        let clone = client.clone();
+        let wait = tokio::spawn(async move { clone.wait_for_disconnect().await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!wait.is_finished());
+
+        client.reconnect();
+
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("disconnect should be observed")
+            .unwrap();
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn hooks_run_on_connect_and_disconnect() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_secs(60));
+
+        let connects = Arc::new(AtomicUsize::new(0));
+        let disconnects = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..2 {
+            let connects = connects.clone();
+            client.on_connect(move |_| {
+                connects.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        let disconnects_hook = disconnects.clone();
+        client.on_disconnect(move || {
+            disconnects_hook.fetch_add(1, Ordering::SeqCst);
+        });
+
+        server.serve();
+        client.init();
+        client.wait_for_client().await.unwrap();
+        assert_eq!(connects.load(Ordering::SeqCst), 2);
+
+        client.reconnect();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(disconnects.load(Ordering::SeqCst), 1);
+        assert_eq!(connects.load(Ordering::SeqCst), 4);
+
+        client.shutdown();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(disconnects.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn album_art_missing() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_secs(60));
+
+        server.serve();
+        client.init();
+
+        // The fake server sends no binary data, as for a song without art.
+        let art = client.album_art("song.flac").await.unwrap();
+        assert!(art.is_none());
+
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn heartbeat_reconnects_unresponsive_server() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClientBuilder::new()
+            .host(server.addr())
+            .retry_interval(Duration::from_millis(10))
+            .heartbeat(Duration::from_millis(50))
+            .build();
+
+        let connections = server.hang();
+        client.init();
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(connections.load(Ordering::SeqCst) > 1);
+
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn command_timeout_reconnects() {
+        let server = FakeServer::bind().await;
+        // The retry delay is only skipped if the timeout triggers a reconnect.
+        let client = PersistentClient::new(server.addr(), Duration::from_secs(60));
+
+        let connections = server.hang();
+        client.init();
+        let first = client.wait_for_client().await.unwrap();
+
+        let res = client
+            .command_with_timeout(commands::Ping, Duration::from_millis(100))
+            .await;
+        assert!(matches!(res, Err(Error::Timeout)));
+
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            client.wait_for_new_client(Some(&first)),
+        )
+        .await
+        .expect("client should reconnect")
+        .unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn idle_timeout_reconnects_on_demand() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClientBuilder::new()
+            .host(server.addr())
+            .idle_timeout(Duration::from_millis(50))
+            .build();
+
+        let connections = server.serve();
+        let mut states = client.subscribe_state();
+        client.init();
+
+        assert_eq!(states.recv().await.unwrap(), ConnectionState::Reconnecting);
+        assert_eq!(states.recv().await.unwrap(), ConnectionState::Connected);
+
+        let state = tokio::time::timeout(Duration::from_secs(5), states.recv())
+            .await
+            .expect("connection should become idle")
+            .unwrap();
+        assert_eq!(state, ConnectionState::Idle);
+        assert!(!client.is_connected());
+
+        tokio::time::timeout(Duration::from_secs(5), client.command(commands::Ping))
+            .await
+            .expect("command should reconnect")
+            .unwrap();
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn host_is_active_address() {
+        let server = FakeServer::bind().await;
+        let addr = server.addr();
+        server.serve();
+
+        let client = PersistentClientBuilder::new()
+            .hosts(["localhost:1", addr.as_str()])
+            .build();
+        assert_eq!(client.host(), "localhost:1");
+
+        client.init();
+        client.wait_for_client().await.unwrap();
+
+        assert_eq!(client.host(), addr);
+        assert_eq!(client.hosts(), ["localhost:1", addr.as_str()]);
+
+        client.shutdown();
+    }
+
+    #[test]
+    fn is_local_resolved_on_first_use() {
+        let client = PersistentClient::new("localhost:6600".to_string(), Duration::from_secs(5));
+        assert!(client.local.get().is_none());
+
+        assert!(client.is_local());
+        assert_eq!(client.clone().local.get(), Some(&true));
+
+        let client = PersistentClientBuilder::new()
+            .hosts(["localhost:6600", "192.0.2.1:6600"])
+            .build();
+        assert!(!client.is_local());
+    }
+
+    #[tokio::test]
+    async fn wait_for_client_timeout_elapses() {
+        let client = PersistentClient::new("localhost:1".to_string(), Duration::from_secs(5));
+        client.init();
+
+        let client = client
+            .wait_for_client_timeout(Duration::from_millis(50))
+            .await;
+        assert!(client.is_none());
+    }
+
+    #[tokio::test]
+    async fn event_stream_ends_on_shutdown() {
+        let client = PersistentClient::new("localhost:1".to_string(), Duration::from_secs(5));
+        let mut events = Box::pin(client.event_stream());
+
+        client.shutdown();
+        assert!(events.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn try_command_disconnected() {
+        let client = PersistentClient::new("localhost:1".to_string(), Duration::from_secs(5));
+
+        let res = client.try_command(commands::Ping).await;
+        assert!(matches!(res, Err(Error::NotConnected)));
+    }
+
+    #[tokio::test]
+    async fn wait_for_client_cancelled() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_millis(50));
+        client.init();
+
+        // The server does not respond until it starts serving,
+        // so the wait is cancelled before a connection is made.
+        let wait = tokio::time::timeout(Duration::from_millis(50), client.wait_for_client());
+        assert!(wait.await.is_err());
+        assert_eq!(client.connection_channel.0.receiver_count(), 1);
+
+        server.serve();
+
+        let wait = tokio::time::timeout(Duration::from_secs(5), client.wait_for_client());
+        assert!(wait.await.is_ok());
+        assert_eq!(client.connection_channel.0.receiver_count(), 1);
+    }
+
+    #[test]
+    fn offset_position_saturates() {
+        let position = Duration::from_secs(10);
+
+        assert_eq!(offset_position(position, 5), Duration::from_secs(15));
+        assert_eq!(offset_position(position, -5), Duration::from_secs(5));
+        assert_eq!(offset_position(position, -30), Duration::ZERO);
+    }
+
+    #[test]
+    fn song_path_stays_in_music_directory() {
+        let dir = Path::new("/music");
+
+        assert_eq!(
+            song_path(dir, "artist/song.flac"),
+            Some(PathBuf::from("/music/artist/song.flac"))
+        );
+        assert_eq!(song_path(dir, "/etc/passwd"), None);
+        assert_eq!(song_path(dir, "../secret.txt"), None);
+        assert_eq!(song_path(dir, "artist/../../secret.txt"), None);
+    }
+
+    #[tokio::test]
+    async fn song_changes_yields_current_song() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_millis(50));
+        server.serve();
+        client.init();
+
+        let mut songs = Box::pin(client.song_changes());
+        assert_eq!(songs.next().await, Some(None));
+
+        client.shutdown();
+        assert_eq!(songs.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn status_stream_polls() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_millis(50));
+        server.serve();
+        client.init();
+
+        let statuses = Box::pin(client.status_stream(Duration::from_millis(10)))
+            .take(3)
+            .collect::<Vec<_>>();
+        let statuses = tokio::time::timeout(Duration::from_secs(5), statuses)
+            .await
+            .expect("Timed out waiting for statuses");
+        assert_eq!(statuses.len(), 3);
+
+        let mut statuses = Box::pin(client.status_stream(Duration::from_millis(10)));
+        client.shutdown();
+        assert!(statuses.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_polling_when_idle_rejected() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_millis(10));
+
+        let idles = Arc::new(AtomicUsize::new(0));
+        let handler_idles = idles.clone();
+        let connections = server.serve_with(move |_, line| {
+            if line == "idle" {
+                handler_idles.fetch_add(1, Ordering::SeqCst);
+                Reply::Raw("ACK [5@0] {idle} unknown command \"idle\"\n".to_string())
+            } else {
+                Reply::Default
+            }
+        });
+        client.init();
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        // Only the first connection sends `idle`, and the second stays open.
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+        assert_eq!(idles.load(Ordering::SeqCst), 1);
+        assert!(client.is_connected());
+        assert!(client.status().await.is_ok());
+
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn rejected_password_retries() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::with_password(
+            server.addr(),
+            Duration::from_millis(10),
+            "wrong".to_string(),
+        );
+
+        let connections = server.serve_with(|_, line| {
+            if line.starts_with("password ") {
+                Reply::Raw("ACK [3@0] {password} incorrect password\n".to_string())
+            } else {
+                Reply::Default
+            }
+        });
+        client.init();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(!client.is_connected());
+        assert!(connections.load(Ordering::SeqCst) > 1);
+        assert!(client.stats().failed_attempts > 1);
+        assert!(client
+            .last_error()
+            .is_some_and(|err| err.starts_with("Password rejected")));
+
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn with_retry_reruns_after_connection_lost() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_millis(10));
+
+        // The first connection is dropped partway through the command.
+        let connections = server.serve_with(|connection, line| match (connection, line) {
+            (1, "ping") => Reply::Close,
+            _ => Reply::Default,
+        });
+        client.init();
+
+        let calls = AtomicUsize::new(0);
+        let res = client
+            .with_retry(2, |client| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async move { client.command(commands::Ping).await }
+            })
+            .await;
+
+        assert!(res.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn command_session_holds_idle() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_secs(60));
+
+        let lines = Arc::new(Mutex::new(vec![]));
+        let handler_lines = lines.clone();
+        server.serve_with(move |_, line| {
+            handler_lines.lock().unwrap().push(line.to_string());
+            Reply::Default
+        });
+        client.init();
+        client.wait_for_client().await.unwrap();
+
+        // Lets the connection settle into idle after its initial commands.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        lines.lock().unwrap().clear();
+
+        client
+            .with_command_session(|session| async move {
+                session.command(commands::Ping).await.unwrap();
+                // Long enough for the connection to re-enter idle without the session.
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                session.command(commands::Ping).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(300)).await;
+            })
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            *lines.lock().unwrap(),
+            ["noidle", "ping", "ping", "idle"].map(String::from)
+        );
+
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn channels_resubscribed_after_reconnect() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_secs(60));
+
+        let subscribes = Arc::new(Mutex::new(vec![]));
+        let handler_subscribes = subscribes.clone();
+        server.serve_with(move |connection, line| {
+            if line.starts_with("subscribe ") {
+                handler_subscribes
+                    .lock()
+                    .unwrap()
+                    .push((connection, line.to_string()));
+            }
+
+            Reply::Default
+        });
+        client.init();
+
+        let first = client.wait_for_client().await.unwrap();
+        client.subscribe_to_channel("updates").await.unwrap();
+
+        client.reconnect();
+        let second = client.wait_for_new_client(Some(&first)).await.unwrap();
+        assert_eq!(
+            *subscribes.lock().unwrap(),
+            [
+                (1, "subscribe updates".to_string()),
+                (2, "subscribe updates".to_string())
+            ]
+        );
+
+        client.unsubscribe_from_channel("updates").await.unwrap();
+        client.reconnect();
+        client.wait_for_new_client(Some(&second)).await.unwrap();
+        assert_eq!(subscribes.lock().unwrap().len(), 2);
+
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn volume_watch_follows_mixer() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_secs(60));
+
+        let volume = Arc::new(AtomicU8::new(50));
+        let mixer_changed = Arc::new(AtomicBool::new(false));
+        let handler_volume = volume.clone();
+        server.serve_with(move |_, line| match line.split_once(' ') {
+            Some(("setvol", value)) => {
+                handler_volume.store(value.parse().unwrap(), Ordering::SeqCst);
+                mixer_changed.store(true, Ordering::SeqCst);
+                Reply::Default
+            }
+            // Changes made since the last `idle` are reported as soon as it is sent.
+            _ if line == "idle" && mixer_changed.swap(false, Ordering::SeqCst) => {
+                Reply::Raw("changed: mixer\nOK\n".to_string())
+            }
+            _ if line == "status" => Reply::Raw(format!(
+                "volume: {}\nstate: stop\nOK\n",
+                handler_volume.load(Ordering::SeqCst)
+            )),
+            _ => Reply::Default,
+        });
+
+        let mut volume_rx = client.volume_watch();
+        assert_eq!(*volume_rx.borrow(), None);
+        client.init();
+
+        let wait = volume_rx.wait_for(|volume| *volume == Some(50));
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("volume should be read on connect")
+            .unwrap();
+
+        client.set_volume(80).await.unwrap();
+        let wait = volume_rx.wait_for(|volume| *volume == Some(80));
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("volume should update on mixer change")
+            .unwrap();
+
+        client.shutdown();
+        let wait = volume_rx.wait_for(Option::is_none);
+        tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .expect("volume should be cleared on disconnect")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn on_queue_empty_resolves_when_cleared() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_secs(60));
+
+        let queue_length = Arc::new(AtomicUsize::new(3));
+        let queue_changed = Arc::new(AtomicBool::new(false));
+        let handler_queue_length = queue_length.clone();
+        server.serve_with(move |_, line| match line {
+            "clear" => {
+                handler_queue_length.store(0, Ordering::SeqCst);
+                queue_changed.store(true, Ordering::SeqCst);
+                Reply::Default
+            }
+            "idle" if queue_changed.swap(false, Ordering::SeqCst) => {
+                Reply::Raw("changed: playlist\nOK\n".to_string())
+            }
+            "status" => Reply::Raw(format!(
+                "playlistlength: {}\nrepeat: 0\nrandom: 0\nconsume: 0\nstate: stop\nOK\n",
+                handler_queue_length.load(Ordering::SeqCst)
+            )),
+            _ => Reply::Default,
+        });
+        client.init();
+        client.wait_for_client().await.unwrap();
+
+        // Gives the client time to read the initial queue length.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let empty = client.on_queue_empty();
+        client.command(commands::ClearQueue).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), empty)
+            .await
+            .expect("queue empty should be observed");
+
+        client.shutdown();
+    }
+
+    #[tokio::test]
+    async fn strict_controls_when_stopped() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::builder()
+            .host(server.addr())
+            .retry_interval(Duration::from_millis(50))
+            .strict_controls(true)
+            .build();
+        server.serve();
+        client.init();
+
+        assert!(matches!(
+            client.toggle_pause().await,
+            Err(Error::NotPlaying)
+        ));
+        assert!(matches!(client.previous().await, Err(Error::NotPlaying)));
+        assert!(client.play().await.is_ok());
+        assert!(client.stop().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn seek_when_stopped() {
+        let server = FakeServer::bind().await;
+        let client = PersistentClient::new(server.addr(), Duration::from_millis(50));
+        server.serve();
+        client.init();
+
+        assert!(matches!(
+            client.seek(Duration::from_secs(10)).await,
+            Err(Error::NotPlaying)
+        ));
+        assert!(matches!(
+            client.seek_relative(-5).await,
+            Err(Error::NotPlaying)
+        ));
+    }
 }
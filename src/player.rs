@@ -0,0 +1,151 @@
+use mpd_client::commands::SingleMode;
+use mpd_client::responses::{PlayState, SongInQueue, Status};
+use std::time::{Duration, Instant};
+
+/// Margin allowed when deciding whether a song played to its end,
+/// to account for the delay between status updates.
+const END_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// The playback options from the server status,
+/// as shown by the repeat, random, single and consume toggles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaybackOptions {
+    pub repeat: bool,
+    pub random: bool,
+    /// Whether playback stops after the current song.
+    /// This can be enabled, disabled, or enabled for a single song only.
+    pub single: SingleMode,
+    pub consume: bool,
+}
+
+impl From<&Status> for PlaybackOptions {
+    fn from(status: &Status) -> Self {
+        Self {
+            repeat: status.repeat,
+            random: status.random,
+            single: status.single,
+            consume: status.consume,
+        }
+    }
+}
+
+/// The current song and server status,
+/// read together so that they are consistent with each other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NowPlaying {
+    /// The current song, if there is one.
+    pub song: Option<SongInQueue>,
+    pub status: Status,
+}
+
+/// Player event derived from the server status
+/// each time the `player` subsystem changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlayerEvent {
+    /// Playback started or resumed.
+    Playing,
+    /// Playback was paused.
+    Paused,
+    /// Playback was stopped by a client.
+    Stopped,
+    /// Playback stopped on its own,
+    /// because the last song in the queue finished
+    /// or `single` mode stopped after the current song.
+    QueueEnded,
+    /// A playback error occurred, such as a song failing to decode.
+    Error(String),
+    /// The previous playback error was cleared.
+    /// The server does this once a song plays successfully, or on `clearerror`.
+    ErrorCleared,
+}
+
+/// Tracks the previous player status
+/// to derive [`PlayerEvent`]s from changes.
+#[derive(Debug, Default)]
+pub(crate) struct PlayerTracker {
+    previous: Option<(Status, Instant)>,
+}
+
+impl PlayerTracker {
+    /// Updates the tracked status,
+    /// returning events for any changes in playback state or error.
+    pub(crate) fn update(&mut self, status: Status) -> Vec<PlayerEvent> {
+        let mut events = vec![];
+
+        if let Some((previous, at)) = self.previous.take() {
+            if previous.state != status.state {
+                events.push(transition(&previous, at.elapsed(), &status));
+            }
+
+            if previous.error != status.error {
+                events.push(match &status.error {
+                    Some(error) => PlayerEvent::Error(error.clone()),
+                    None => PlayerEvent::ErrorCleared,
+                });
+            }
+        }
+
+        self.previous = Some((status, Instant::now()));
+        events
+    }
+}
+
+fn transition(previous: &Status, since: Duration, current: &Status) -> PlayerEvent {
+    match current.state {
+        PlayState::Playing => PlayerEvent::Playing,
+        PlayState::Paused => PlayerEvent::Paused,
+        PlayState::Stopped => {
+            let last_song = previous.next_song.is_none() || previous.single != SingleMode::Disabled;
+
+            if previous.state == PlayState::Playing
+                && last_song
+                && reached_end(previous.elapsed, previous.duration, since)
+            {
+                PlayerEvent::QueueEnded
+            } else {
+                PlayerEvent::Stopped
+            }
+        }
+    }
+}
+
+/// Checks whether a song which was at `elapsed` of `duration`
+/// will have reached its end after playing for `since`.
+///
+/// This is always false for songs with an unknown duration, such as streams.
+fn reached_end(elapsed: Option<Duration>, duration: Option<Duration>, since: Duration) -> bool {
+    match (elapsed, duration) {
+        (Some(elapsed), Some(duration)) => elapsed + since + END_TOLERANCE >= duration,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reached_end_near_duration() {
+        let duration = Some(Duration::from_secs(180));
+
+        assert!(reached_end(
+            Some(Duration::from_secs(170)),
+            duration,
+            Duration::from_secs(9)
+        ));
+        assert!(!reached_end(
+            Some(Duration::from_secs(60)),
+            duration,
+            Duration::from_secs(9)
+        ));
+    }
+
+    #[test]
+    fn reached_end_unknown_duration() {
+        assert!(!reached_end(
+            Some(Duration::from_secs(600)),
+            None,
+            Duration::from_secs(600)
+        ));
+    }
+}
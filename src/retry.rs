@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+/// Strategy for the delay between connection attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryStrategy {
+    /// Waits the same interval after every attempt.
+    Fixed(Duration),
+    /// Doubles the delay after each consecutive failure.
+    /// See [`BackoffConfig`].
+    Backoff(BackoffConfig),
+}
+
+/// Configuration for exponential backoff between connection attempts.
+///
+/// The delay starts at `base` and doubles after each consecutive failure,
+/// up to `max`. It resets to `base` once a connection succeeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackoffConfig {
+    pub base: Duration,
+    pub max: Duration,
+    /// Maximum random time added to each delay.
+    /// This avoids several clients reconnecting at the same moment.
+    pub jitter: Duration,
+}
+
+impl RetryStrategy {
+    /// Gets the delay before the next connection attempt,
+    /// after `failures` consecutive failed attempts.
+    ///
+    /// `failures` is zero after a lost connection,
+    /// so the retry after the first failure also waits the base delay.
+    pub(crate) fn delay(&self, failures: u32) -> Duration {
+        match self {
+            RetryStrategy::Fixed(interval) => *interval,
+            RetryStrategy::Backoff(config) => {
+                config.backoff(failures.saturating_sub(1)) + config.jitter.mul_f64(fastrand::f64())
+            }
+        }
+    }
+}
+
+impl BackoffConfig {
+    /// Gets the delay without jitter after `failures` consecutive failures.
+    fn backoff(&self, failures: u32) -> Duration {
+        let multiplier = 2u32.saturating_pow(failures);
+
+        self.base
+            .checked_mul(multiplier)
+            .map_or(self.max, |delay| delay.min(self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_to_max() {
+        let config = BackoffConfig {
+            base: Duration::from_secs(1),
+            max: Duration::from_secs(10),
+            jitter: Duration::ZERO,
+        };
+
+        assert_eq!(config.backoff(0), Duration::from_secs(1));
+        assert_eq!(config.backoff(1), Duration::from_secs(2));
+        assert_eq!(config.backoff(3), Duration::from_secs(8));
+        assert_eq!(config.backoff(4), Duration::from_secs(10));
+        assert_eq!(config.backoff(u32::MAX), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn first_retry_waits_base() {
+        let strategy = RetryStrategy::Backoff(BackoffConfig {
+            base: Duration::from_secs(1),
+            max: Duration::from_secs(10),
+            jitter: Duration::ZERO,
+        });
+
+        assert_eq!(strategy.delay(0), Duration::from_secs(1));
+        assert_eq!(strategy.delay(1), Duration::from_secs(1));
+        assert_eq!(strategy.delay(2), Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,37 @@
+use mpd_client::client::CommandError;
+use mpd_client::commands::{Command, CommandList};
+use mpd_client::Client;
+use std::sync::Arc;
+
+/// Handle for running a sequence of commands on a single connection.
+///
+/// Created by [`PersistentClient::with_command_session`](crate::PersistentClient::with_command_session).
+///
+/// Idle is held off for as long as the session's callback runs,
+/// so the connection leaves idle once at the start
+/// and re-enters it once at the end.
+#[derive(Debug, Clone)]
+pub struct CommandSession {
+    client: Arc<Client>,
+}
+
+impl CommandSession {
+    pub(crate) fn new(client: Arc<Client>) -> Self {
+        Self { client }
+    }
+
+    /// Runs the provided command on the session's connection.
+    pub async fn command<C: Command>(&self, cmd: C) -> Result<C::Response, CommandError> {
+        self.client.command(cmd).await
+    }
+
+    /// Runs the provided command list on the session's connection.
+    pub async fn command_list<L: CommandList>(&self, list: L) -> Result<L::Response, CommandError> {
+        self.client.command_list(list).await
+    }
+
+    /// Gets the underlying client.
+    pub fn client(&self) -> &Arc<Client> {
+        &self.client
+    }
+}
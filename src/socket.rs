@@ -1,35 +1,303 @@
+use crate::no_idle::{IdleHold, NoIdleStream};
 use mpd_client::client::Connection;
 use mpd_client::protocol::MpdProtocolError;
 use mpd_client::Client;
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+#[cfg(unix)]
 use std::os::unix::fs::FileTypeExt;
-use std::path::PathBuf;
-use tokio::net::{TcpStream, UnixStream};
-
-/// Cycles through each MPD host and
-/// returns the first one which connects,
-/// or none if there are none
-pub(crate) async fn try_get_connection(host: &str) -> Result<Connection, MpdProtocolError> {
-    if is_unix_socket(host) {
-        connect_unix(host).await
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+/// Byte stream to a server, over any transport.
+pub(crate) trait Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> Stream for T {}
+
+/// Prefix for hosts which are abstract unix sockets.
+const ABSTRACT_PREFIX: char = '@';
+
+/// Prefix for hosts which are connected to over TLS.
+pub(crate) const TLS_PREFIX: &str = "tls://";
+
+/// Options applied to the underlying socket when connecting.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConnectionOptions {
+    /// Whether to set `TCP_NODELAY` on TCP connections.
+    pub(crate) tcp_nodelay: bool,
+    /// Idle time before sending TCP keepalive probes.
+    /// Keepalive is disabled when `None`.
+    pub(crate) tcp_keepalive: Option<Duration>,
+    /// Hook receiving the raw protocol lines.
+    #[cfg(feature = "debug-io")]
+    pub(crate) io_hook: Option<crate::io_debug::IoHook>,
+    /// TLS config to use for all TCP connections.
+    #[cfg(feature = "tls")]
+    pub(crate) tls: Option<crate::tls::TlsConfig>,
+}
+
+/// Opens a connection to the host and starts the MPD protocol on it.
+///
+/// The stream is wrapped in a [`NoIdleStream`].
+/// If `no_idle` is set, `idle` is never sent to the server,
+/// and otherwise it is only held back while `idle_hold` is held.
+pub(crate) async fn try_get_connection(
+    host: &str,
+    options: &ConnectionOptions,
+    no_idle: bool,
+    idle_hold: &Arc<IdleHold>,
+) -> Result<Connection, MpdProtocolError> {
+    let stream = open_stream(host, options).await?;
+
+    if no_idle {
+        Client::connect(NoIdleStream::new(stream)).await
     } else {
-        connect_tcp(host).await
+        Client::connect(NoIdleStream::with_hold(stream, idle_hold.clone())).await
     }
 }
 
+/// Opens a TCP, unix socket or abstract socket stream to the host,
+/// without starting the MPD protocol.
+pub(crate) async fn open_stream(
+    host: &str,
+    options: &ConnectionOptions,
+) -> io::Result<Box<dyn Stream>> {
+    let stream: Box<dyn Stream> = match host.strip_prefix(TLS_PREFIX) {
+        Some(host) => connect_tls(host, options).await?,
+        #[cfg(unix)]
+        None if is_unix_socket(host) => Box::new(UnixStream::connect(host).await?),
+        #[cfg(unix)]
+        None if host.starts_with(ABSTRACT_PREFIX) => Box::new(connect_abstract(&host[1..])?),
+        #[cfg(feature = "tls")]
+        None if options.tls.is_some() => connect_tls(host, options).await?,
+        None => Box::new(connect_tcp(host, options).await?),
+    };
+
+    #[cfg(feature = "debug-io")]
+    if let Some(hook) = &options.io_hook {
+        return Ok(Box::new(crate::io_debug::DebugStream::new(
+            stream,
+            hook.clone(),
+        )));
+    }
+
+    Ok(stream)
+}
+
+/// Finds the socket of a server running on this machine,
+/// checking `$XDG_RUNTIME_DIR/mpd/socket` and then `/run/mpd/socket`.
+///
+/// Falls back to the localhost TCP address if neither exists.
+pub(crate) fn find_local_host() -> String {
+    let runtime_socket = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join("mpd").join("socket"));
+    let candidates = runtime_socket
+        .into_iter()
+        .chain([PathBuf::from("/run/mpd/socket")]);
+
+    first_socket(candidates).unwrap_or_else(|| "localhost:6600".to_string())
+}
+
+/// Gets the first path which is a unix socket.
+fn first_socket(candidates: impl IntoIterator<Item = PathBuf>) -> Option<String> {
+    candidates
+        .into_iter()
+        .filter_map(|path| path.to_str().map(ToString::to_string))
+        .find(|path| is_unix_socket(path))
+}
+
+#[cfg(unix)]
 fn is_unix_socket(host: &str) -> bool {
     let path = PathBuf::from(host);
     path.exists()
         && path
             .metadata()
-            .map_or(false, |metadata| metadata.file_type().is_socket())
+            .is_ok_and(|metadata| metadata.file_type().is_socket())
+}
+
+/// Unix sockets are not supported on this platform,
+/// so all hosts are connected to over TCP.
+#[cfg(not(unix))]
+fn is_unix_socket(_host: &str) -> bool {
+    false
+}
+
+/// Checks whether the host is a unix socket,
+/// or a TCP address which only resolves to loopback addresses.
+///
+/// Hostnames are resolved using the system resolver,
+/// so this may block.
+pub(crate) fn is_local(host: &str) -> bool {
+    let host = host.strip_prefix(TLS_PREFIX).unwrap_or(host);
+
+    if is_unix_socket(host) || Path::new(host).is_absolute() || host.starts_with(ABSTRACT_PREFIX) {
+        return true;
+    }
+
+    match host.to_socket_addrs() {
+        Ok(addrs) => {
+            let mut addrs = addrs.peekable();
+            addrs.peek().is_some() && addrs.all(|addr| addr.ip().is_loopback())
+        }
+        Err(_) => false,
+    }
 }
 
-async fn connect_unix(host: &str) -> Result<Connection, MpdProtocolError> {
-    let connection = UnixStream::connect(host).await?;
-    Client::connect(connection).await
+/// Connects to the first address the host resolves to which accepts the connection.
+///
+/// The host is resolved again on every call,
+/// so that a server whose address changed is found after reconnecting.
+async fn connect_tcp(host: &str, options: &ConnectionOptions) -> io::Result<TcpStream> {
+    let mut last_err = io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("'{host}' did not resolve to any address"),
+    );
+
+    for addr in resolve(host).await? {
+        match TcpStream::connect(addr).await {
+            Ok(connection) => return configure_tcp(connection, options),
+            Err(err) => last_err = err,
+        }
+    }
+
+    Err(last_err)
 }
 
-async fn connect_tcp(host: &str) -> Result<Connection, MpdProtocolError> {
-    let connection = TcpStream::connect(host).await?;
-    Client::connect(connection).await
+fn configure_tcp(connection: TcpStream, options: &ConnectionOptions) -> io::Result<TcpStream> {
+    connection.set_nodelay(options.tcp_nodelay)?;
+    if let Some(time) = options.tcp_keepalive {
+        SockRef::from(&connection).set_tcp_keepalive(&TcpKeepalive::new().with_time(time))?;
+    }
+
+    Ok(connection)
+}
+
+/// Resolves the host to its current socket addresses.
+async fn resolve(host: &str) -> io::Result<Vec<SocketAddr>> {
+    Ok(tokio::net::lookup_host(host).await?.collect())
+}
+
+/// Connects to the abstract unix socket with the given name.
+///
+/// Abstract sockets are only supported on Linux and Android.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn connect_abstract(name: &str) -> io::Result<UnixStream> {
+    #[cfg(target_os = "android")]
+    use std::os::android::net::SocketAddrExt;
+    #[cfg(target_os = "linux")]
+    use std::os::linux::net::SocketAddrExt;
+
+    let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+
+    // Connecting to a local socket does not block for any meaningful time.
+    let stream = std::os::unix::net::UnixStream::connect_addr(&addr)?;
+    stream.set_nonblocking(true)?;
+
+    UnixStream::from_std(stream)
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn connect_abstract(_name: &str) -> io::Result<UnixStream> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Abstract sockets are only supported on Linux",
+    ))
+}
+
+#[cfg(feature = "tls")]
+async fn connect_tls(host: &str, options: &ConnectionOptions) -> io::Result<Box<dyn Stream>> {
+    let stream = connect_tcp(host, options).await?;
+    let config = options.tls.clone().unwrap_or_default();
+
+    Ok(Box::new(crate::tls::connect(stream, host, &config).await?))
+}
+
+#[cfg(not(feature = "tls"))]
+async fn connect_tls(_host: &str, _options: &ConnectionOptions) -> io::Result<Box<dyn Stream>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "TLS connections require the `tls` feature",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_local_hosts() {
+        #[cfg(unix)]
+        assert!(is_local("/run/mpd/socket"));
+        assert!(is_local("127.0.0.1:6600"));
+        assert!(is_local("[::1]:6600"));
+        assert!(is_local("localhost:6600"));
+        assert!(is_local("tls://localhost:6600"));
+        assert!(is_local("@mpd"));
+
+        assert!(!is_local("192.0.2.1:6600"));
+        assert!(!is_local("not a host"));
+    }
+
+    #[tokio::test]
+    async fn connect_tcp_resolves_hostname() {
+        let addrs = resolve("localhost:6600").await.unwrap();
+        assert!(!addrs.is_empty());
+        assert!(addrs.iter().all(|addr| addr.ip().is_loopback()));
+
+        // `localhost` may resolve to `::1` first, which the listener is not bound to,
+        // so this also covers falling back to the next address.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let host = format!("localhost:{port}");
+        assert!(connect_tcp(&host, &ConnectionOptions::default())
+            .await
+            .is_ok());
+        assert!(connect_tcp("not a host", &ConnectionOptions::default())
+            .await
+            .is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn open_abstract_socket() {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::net::{SocketAddr, UnixListener};
+
+        let name = format!("mpd-utils-{}", std::process::id());
+        let addr = SocketAddr::from_abstract_name(&name).unwrap();
+        let _listener = UnixListener::bind_addr(&addr).unwrap();
+
+        let host = format!("@{name}");
+        assert!(open_stream(&host, &ConnectionOptions::default())
+            .await
+            .is_ok());
+        assert!(
+            open_stream("@mpd-utils-missing", &ConnectionOptions::default())
+                .await
+                .is_err()
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn first_socket_skips_missing() {
+        let dir = std::env::temp_dir().join(format!("mpd-utils-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let socket = dir.join("socket");
+        let _ = std::fs::remove_file(&socket);
+        let _listener = tokio::net::UnixListener::bind(&socket).unwrap();
+
+        let found = first_socket([dir.join("missing"), dir.clone(), socket.clone()]);
+        assert_eq!(found.as_deref(), socket.to_str());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
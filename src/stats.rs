@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Snapshot of the connection statistics of a [`PersistentClient`](crate::PersistentClient).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientStats {
+    /// Number of successful connections after the first.
+    pub reconnects: u64,
+    /// Total number of connection attempts which failed.
+    pub failed_attempts: u64,
+    /// When the most recent connection was established,
+    /// or `None` if the client has never connected.
+    pub last_connected: Option<Instant>,
+}
+
+/// Connection statistics, updated by the background task.
+#[derive(Debug)]
+pub(crate) struct StatsCounters {
+    /// Reference point for `last_connected`.
+    created: Instant,
+    reconnects: AtomicU64,
+    failed_attempts: AtomicU64,
+    /// Microseconds from `created` to the last connection, plus one.
+    /// This is `0` if the client has never connected.
+    last_connected: AtomicU64,
+}
+
+impl StatsCounters {
+    pub(crate) fn new() -> Self {
+        Self {
+            created: Instant::now(),
+            reconnects: AtomicU64::new(0),
+            failed_attempts: AtomicU64::new(0),
+            last_connected: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record_connected(&self) {
+        let since_created = self.created.elapsed().as_micros() as u64 + 1;
+
+        if self.last_connected.swap(since_created, Ordering::Relaxed) != 0 {
+            self.reconnects.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_failure(&self) {
+        self.failed_attempts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> ClientStats {
+        let last_connected = match self.last_connected.load(Ordering::Relaxed) {
+            0 => None,
+            micros => Some(self.created + Duration::from_micros(micros - 1)),
+        };
+
+        ClientStats {
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            failed_attempts: self.failed_attempts.load(Ordering::Relaxed),
+            last_connected,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_reconnects() {
+        let stats = StatsCounters::new();
+        assert_eq!(stats.snapshot().last_connected, None);
+
+        stats.record_failure();
+        stats.record_connected();
+        let first = stats.snapshot();
+        assert_eq!(first.reconnects, 0);
+        assert_eq!(first.failed_attempts, 1);
+        assert!(first.last_connected.is_some());
+
+        stats.record_connected();
+        let second = stats.snapshot();
+        assert_eq!(second.reconnects, 1);
+        assert!(second.last_connected >= first.last_connected);
+    }
+}
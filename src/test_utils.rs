@@ -0,0 +1,173 @@
+//! Utilities for testing against a fake MPD server.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::spawn;
+
+/// Response of a [`FakeServer`] to a single command.
+pub(crate) enum Reply {
+    /// The built-in response.
+    Default,
+    /// The given raw response, including the final `OK` or `ACK` line.
+    Raw(String),
+    /// Closes the connection without responding.
+    Close,
+}
+
+type Handler = dyn Fn(usize, &str) -> Reply + Send + Sync;
+
+/// Minimal fake MPD server,
+/// which replies `OK` to every command.
+///
+/// `status` is answered with a stopped player and an empty queue.
+pub(crate) struct FakeServer {
+    listener: TcpListener,
+    connections: Arc<AtomicUsize>,
+}
+
+impl FakeServer {
+    /// Binds the server to a random local port.
+    /// Connections are not accepted until [`FakeServer::serve`] is called.
+    pub(crate) async fn bind() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind fake server");
+
+        Self {
+            listener,
+            connections: Arc::default(),
+        }
+    }
+
+    /// Gets the address the server is bound to.
+    pub(crate) fn addr(&self) -> String {
+        self.listener
+            .local_addr()
+            .expect("Failed to get fake server address")
+            .to_string()
+    }
+
+    /// Starts accepting connections in the background.
+    /// Returns a counter of the connections accepted so far.
+    pub(crate) fn serve(self) -> Arc<AtomicUsize> {
+        self.serve_with(|_, _| Reply::Default)
+    }
+
+    /// Starts accepting connections in the background,
+    /// replying to each command as decided by `handler`.
+    ///
+    /// The handler receives the number of the connection, starting from `1`,
+    /// and the command line.
+    /// Returns a counter of the connections accepted so far.
+    pub(crate) fn serve_with<F>(self, handler: F) -> Arc<AtomicUsize>
+    where
+        F: Fn(usize, &str) -> Reply + Send + Sync + 'static,
+    {
+        let connections = self.connections.clone();
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        spawn(async move {
+            while let Ok((stream, _)) = self.listener.accept().await {
+                let connection = self.connections.fetch_add(1, Ordering::SeqCst) + 1;
+                spawn(handle_connection(stream, connection, handler.clone()));
+            }
+        });
+
+        connections
+    }
+
+    /// Starts accepting TLS connections in the background,
+    /// using `acceptor` for the handshake and replying `OK` to every command.
+    /// Returns a counter of the connections accepted so far.
+    #[cfg(feature = "tls")]
+    pub(crate) fn serve_tls(self, acceptor: tokio_rustls::TlsAcceptor) -> Arc<AtomicUsize> {
+        let connections = self.connections.clone();
+        let handler: Arc<Handler> = Arc::new(|_, _| Reply::Default);
+
+        spawn(async move {
+            while let Ok((stream, _)) = self.listener.accept().await {
+                let connection = self.connections.fetch_add(1, Ordering::SeqCst) + 1;
+                let acceptor = acceptor.clone();
+                let handler = handler.clone();
+
+                spawn(async move {
+                    if let Ok(stream) = acceptor.accept(stream).await {
+                        handle_connection(stream, connection, handler).await;
+                    }
+                });
+            }
+        });
+
+        connections
+    }
+
+    /// Starts accepting connections in the background,
+    /// sending the greeting but never responding to any command,
+    /// as if the network dropped out.
+    /// Returns a counter of the connections accepted so far.
+    pub(crate) fn hang(self) -> Arc<AtomicUsize> {
+        let connections = self.connections.clone();
+
+        spawn(async move {
+            while let Ok((mut stream, _)) = self.listener.accept().await {
+                self.connections.fetch_add(1, Ordering::SeqCst);
+
+                spawn(async move {
+                    if stream.write_all(b"OK MPD 0.23.5\n").await.is_ok() {
+                        // Keeps the connection open without reading from it.
+                        std::future::pending::<()>().await;
+                    }
+                });
+            }
+        });
+
+        connections
+    }
+
+    /// Starts accepting connections in the background,
+    /// closing each one immediately.
+    /// Returns a counter of the connections accepted so far.
+    pub(crate) fn reject(self) -> Arc<AtomicUsize> {
+        let connections = self.connections.clone();
+
+        spawn(async move {
+            while let Ok((stream, _)) = self.listener.accept().await {
+                self.connections.fetch_add(1, Ordering::SeqCst);
+                drop(stream);
+            }
+        });
+
+        connections
+    }
+}
+
+async fn handle_connection<S>(stream: S, connection: usize, handler: Arc<Handler>)
+where
+    S: AsyncRead + AsyncWrite,
+{
+    let (read, mut write) = tokio::io::split(stream);
+
+    if write.write_all(b"OK MPD 0.23.5\n").await.is_err() {
+        return;
+    }
+
+    let mut lines = BufReader::new(read).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let response = match handler(connection, &line) {
+            Reply::Raw(response) => response,
+            Reply::Close => return,
+            // `idle` is only answered once it is cancelled by `noidle`.
+            Reply::Default if line == "idle" => continue,
+            Reply::Default if line == "status" => {
+                "repeat: 0\nrandom: 0\nconsume: 0\nstate: stop\nOK\n".to_string()
+            }
+            Reply::Default => "OK\n".to_string(),
+        };
+
+        if write.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}